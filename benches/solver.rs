@@ -0,0 +1,102 @@
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use fluid_simulation::{boundary::Boundary, config::Config, fluid::Fluid, simulation::Simulation};
+use std::hint::black_box;
+
+const PARTICLE_COUNTS: [u32; 3] = [1_000, 10_000, 100_000];
+/// Steps run before measuring, so that the particles have left the spawn
+/// lattice and neighbour counts look like those of a running simulation.
+const WARM_UP_STEPS: u32 = 10;
+const STEP_SEED: u64 = 0;
+
+/// A fluid that has run for `WARM_UP_STEPS` steps from the default config
+/// with `particle_count` particles. The domain is scaled with the count so
+/// that the fluid fills the same share of it at every size.
+fn settled_fluid(particle_count: u32) -> (Config, Fluid, Boundary) {
+    let mut config = Config::default();
+    let scale = (particle_count as f32 / config.particle_count as f32).sqrt();
+    config.particle_count = particle_count;
+    config.domain_width *= scale;
+    config.domain_height *= scale;
+    config.validate().expect("benchmark config is invalid");
+    config.adapt_to_fluid_type();
+
+    let mut simulation = Simulation::new(config);
+    for _ in 0..WARM_UP_STEPS {
+        simulation.step();
+    }
+    (
+        simulation.config().clone(),
+        simulation.fluid().clone(),
+        simulation.boundary().clone(),
+    )
+}
+
+fn solver(c: &mut Criterion) {
+    let fluids: Vec<(u32, Config, Fluid, Boundary)> = PARTICLE_COUNTS
+        .into_iter()
+        .map(|particle_count| {
+            let (config, fluid, boundary) = settled_fluid(particle_count);
+            (particle_count, config, fluid, boundary)
+        })
+        .collect();
+
+    // Throughput counts every particle, ghosts included, since they all go
+    // through the grid and the density and force passes
+    let mut group = c.benchmark_group("update_spatial_grid");
+    for (particle_count, _, fluid, _) in &fluids {
+        let mut fluid = fluid.clone();
+        group.throughput(Throughput::Elements(fluid.particles.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(particle_count), |b| {
+            b.iter(|| fluid.update_spatial_grid())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("update_density");
+    // An iteration at the largest size takes a sizeable fraction of a second
+    group.sample_size(10);
+    for (particle_count, config, fluid, _) in &fluids {
+        let mut fluid = fluid.clone();
+        group.throughput(Throughput::Elements(fluid.particles.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(particle_count), |b| {
+            b.iter(|| fluid.update_density(black_box(config)))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("calculate_forces");
+    group.sample_size(10);
+    for (particle_count, config, fluid, _) in &fluids {
+        group.throughput(Throughput::Elements(fluid.particles.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(particle_count), |b| {
+            b.iter(|| fluid.calculate_forces(black_box(config), STEP_SEED))
+        });
+    }
+    group.finish();
+
+    // Collisions are resolved in place, so every iteration starts from a
+    // fresh copy of the particles moved one step on from the settled state,
+    // with those that reached the walls not yet pushed back
+    let mut group = c.benchmark_group("check_collision");
+    for (particle_count, config, fluid, boundary) in &fluids {
+        let mut unresolved = fluid.particles.clone();
+        for particle in &mut unresolved {
+            particle.update(config.time_step, config.gravity);
+        }
+        group.throughput(Throughput::Elements(unresolved.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(particle_count), |b| {
+            b.iter_batched(
+                || unresolved.clone(),
+                |mut particles| {
+                    boundary.check_collision(black_box(&mut particles));
+                    particles
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, solver);
+criterion_main!(benches);
@@ -0,0 +1,491 @@
+use fluid_simulation::{
+    checkpoint::Checkpoint,
+    colormap::ParticleColors,
+    config::{Config, FluidType, InteractionType},
+    profiler::{Phase, TraceWriter},
+    raster::RenderMode,
+    replay::{InputEvent, Player, Recorder},
+    simulation::Simulation,
+    surface::Surface,
+};
+use macroquad::prelude::*;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config_watcher::ConfigWatcher;
+use crate::overlay::DebugOverlays;
+use crate::panel::{PanelAction, ParameterPanel};
+use crate::profile_hud::ProfileHud;
+use crate::render;
+use crate::view::View;
+
+const QUICK_CHECKPOINT_PATH: &str = "checkpoint.json";
+const RECORDING_PATH: &str = "replay.json";
+const STATUS_DURATION: f32 = 3.0;
+const FRAME_ADVANCE_STEPS: u32 = 10;
+
+/// A message shown in the top left corner, either for a few seconds or, when
+/// `remaining_time` is `None`, until it is replaced.
+struct StatusMessage {
+    text: String,
+    color: Color,
+    remaining_time: Option<f32>,
+}
+
+pub struct App {
+    is_running: bool,
+    is_paused: bool,
+    simulation: Simulation,
+    config_watcher: ConfigWatcher,
+    panel: ParameterPanel,
+    view: View,
+    overlays: DebugOverlays,
+    render_mode: RenderMode,
+    status: Option<StatusMessage>,
+    /// Last checkpoint saved or loaded, or the starting state if there has
+    /// been none, for resetting without going through the file.
+    last_checkpoint: Checkpoint,
+    recorder: Option<Recorder>,
+    player: Option<Player>,
+    profile_hud: ProfileHud,
+    trace: Option<TraceWriter>,
+    frame_count: u64,
+}
+
+impl App {
+    /// With a `player`, the simulation is expected to start from the replay's
+    /// initial state and the recorded input is played back. With a `trace`,
+    /// the phase timings of every frame are written to it.
+    pub fn new(
+        simulation: Simulation,
+        config_watcher: ConfigWatcher,
+        player: Option<Player>,
+        trace: Option<TraceWriter>,
+    ) -> Self {
+        let panel = ParameterPanel::new(simulation.config().clone());
+        let last_checkpoint = simulation.to_checkpoint();
+        Self {
+            is_running: true,
+            is_paused: player.as_ref().is_none_or(Player::starts_paused),
+            simulation,
+            config_watcher,
+            panel,
+            view: View::new(),
+            overlays: DebugOverlays::new(),
+            render_mode: RenderMode::Particles,
+            status: None,
+            last_checkpoint,
+            recorder: None,
+            player,
+            profile_hud: ProfileHud::new(),
+            trace,
+            frame_count: 0,
+        }
+    }
+
+    pub fn handle_input(&mut self) {
+        if is_key_pressed(KeyCode::Escape) {
+            self.is_running = false;
+        }
+        if is_key_pressed(KeyCode::Space) {
+            self.apply_input(InputEvent::TogglePause);
+        }
+        if self.is_paused && is_key_pressed(KeyCode::Right) {
+            let steps = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                FRAME_ADVANCE_STEPS
+            } else {
+                1
+            };
+            match &mut self.player {
+                // Stepping through a playback still applies the recorded input
+                Some(player) => {
+                    for _ in 0..steps {
+                        player.apply_due(&mut self.simulation);
+                        self.simulation.step();
+                    }
+                }
+                None => self.apply_input(InputEvent::Step(steps)),
+            }
+        }
+        if is_key_pressed(KeyCode::F5) {
+            self.save_checkpoint();
+        }
+        if is_key_pressed(KeyCode::F6) {
+            self.toggle_recording();
+        }
+        if is_key_pressed(KeyCode::F9) {
+            self.end_session();
+            self.load_checkpoint();
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            self.panel.toggle();
+        }
+        if is_key_pressed(KeyCode::G) {
+            self.switch_fluid_type();
+        }
+        self.overlays.handle_input();
+        self.profile_hud.handle_input();
+        if is_key_pressed(KeyCode::M) {
+            self.render_mode = self.render_mode.next();
+        }
+        if is_key_pressed(KeyCode::C) {
+            let color_map = &mut self.simulation.config_mut().color_map;
+            color_map.color_by = color_map.color_by.next();
+        }
+        if is_key_pressed(KeyCode::P) {
+            let color_map = &mut self.simulation.config_mut().color_map;
+            color_map.palette = color_map.palette.next();
+        }
+        if is_key_pressed(KeyCode::A) {
+            let color_map = &mut self.simulation.config_mut().color_map;
+            color_map.auto_range = !color_map.auto_range;
+        }
+        if is_key_pressed(KeyCode::Home) {
+            self.view.reset();
+        }
+        if is_key_pressed(KeyCode::R) {
+            self.end_session();
+            if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                self.simulation = Simulation::from_checkpoint(self.last_checkpoint.clone());
+            } else {
+                self.simulation.reset();
+            }
+        }
+
+        match self.panel.draw(self.simulation.config()) {
+            Some(PanelAction::SwitchFluidType) => self.switch_fluid_type(),
+            Some(PanelAction::Changed(config)) => self.change_config(config),
+            None => {}
+        }
+        // Clicks on the panel should not also pull or push the fluid
+        let is_over_panel = self.panel.is_mouse_over();
+        let domain_size = self.simulation.boundary().domain_size();
+        self.view.handle_input(domain_size, !is_over_panel);
+
+        // During playback the fluid only follows the recorded input
+        if self.player.is_some() {
+            return;
+        }
+
+        if !is_over_panel && is_mouse_button_down(MouseButton::Left) {
+            let mouse_pos = self.camera().screen_to_world(mouse_position().into());
+            self.apply_input(InputEvent::Interact {
+                point: mouse_pos,
+                interaction_type: InteractionType::Pull,
+            });
+        }
+
+        if !is_over_panel && is_mouse_button_down(MouseButton::Right) {
+            let mouse_pos = self.camera().screen_to_world(mouse_position().into());
+            self.apply_input(InputEvent::Interact {
+                point: mouse_pos,
+                interaction_type: InteractionType::Push,
+            });
+        }
+
+        if is_mouse_button_released(MouseButton::Left) {
+            self.apply_input(InputEvent::ClearInteraction);
+        }
+
+        if is_mouse_button_released(MouseButton::Right) {
+            self.apply_input(InputEvent::ClearInteraction);
+        }
+    }
+
+    /// Applies a user input to the simulation, and records it if a recording
+    /// is running.
+    fn apply_input(&mut self, event: InputEvent) {
+        // Recorded with the step count it was applied at, which `Step` moves on
+        let step = self.simulation.step_count();
+        match &event {
+            InputEvent::Interact {
+                point,
+                interaction_type,
+            } => self.simulation.set_interaction(*point, *interaction_type),
+            InputEvent::ClearInteraction => self.simulation.clear_interaction(),
+            InputEvent::SetFluidType(fluid_type) => {
+                if let Err(e) = self.simulation.set_fluid_type(*fluid_type) {
+                    self.set_status(format!("Cannot switch fluid type: {}", e), RED);
+                    return;
+                }
+            }
+            InputEvent::TogglePause => self.is_paused = !self.is_paused,
+            InputEvent::Step(steps) => {
+                for _ in 0..*steps {
+                    self.simulation.step();
+                }
+            }
+            InputEvent::ConfigChanged(config) => self.simulation.apply_tunable_config(config),
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(step, event);
+        }
+    }
+
+    /// Applies the runtime parameters of `config`, unless a playback is
+    /// running, which only follows the recorded changes.
+    fn change_config(&mut self, config: Box<Config>) {
+        if self.player.is_some() {
+            self.set_status(
+                "Parameters cannot be changed during playback".to_owned(),
+                RED,
+            );
+            return;
+        }
+        self.apply_input(InputEvent::ConfigChanged(config));
+    }
+
+    fn toggle_recording(&mut self) {
+        if self.player.is_some() {
+            self.set_status("Cannot record during playback".to_owned(), RED);
+            return;
+        }
+        match self.recorder.take() {
+            Some(recorder) => self.save_recording(recorder),
+            None => {
+                self.recorder = Some(Recorder::start(&self.simulation, self.is_paused));
+                self.set_status("Recording input".to_owned(), GREEN);
+            }
+        }
+    }
+
+    fn save_recording(&mut self, recorder: Recorder) {
+        let path = Path::new(RECORDING_PATH);
+        let event_count = recorder.event_count();
+        match recorder.finish().save(path) {
+            Ok(()) => self.set_status(
+                format!(
+                    "Saved {} recorded events to '{}'",
+                    event_count,
+                    path.display()
+                ),
+                GREEN,
+            ),
+            Err(e) => self.set_status(
+                format!("Failed to save recording '{}': {}", path.display(), e),
+                RED,
+            ),
+        }
+    }
+
+    /// Ends a recording or playback before the simulation is replaced, since
+    /// the recorded input would no longer apply to it.
+    fn end_session(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            self.save_recording(recorder);
+        }
+        if self.player.take().is_some() {
+            self.set_status("Playback stopped".to_owned(), GREEN);
+        }
+    }
+
+    fn set_status(&mut self, text: String, color: Color) {
+        self.status = Some(StatusMessage {
+            text,
+            color,
+            remaining_time: Some(STATUS_DURATION),
+        });
+    }
+
+    fn save_checkpoint(&mut self) {
+        let path = Path::new(QUICK_CHECKPOINT_PATH);
+        let checkpoint = self.simulation.to_checkpoint();
+        match checkpoint.save(path) {
+            Ok(()) => {
+                println!("Saved checkpoint to '{}'", path.display());
+                self.last_checkpoint = checkpoint;
+            }
+            Err(e) => eprintln!("Failed to save checkpoint '{}': {}", path.display(), e),
+        }
+    }
+
+    fn load_checkpoint(&mut self) {
+        let path = Path::new(QUICK_CHECKPOINT_PATH);
+        match Checkpoint::load(path) {
+            Ok(checkpoint) => {
+                self.last_checkpoint = checkpoint.clone();
+                self.simulation = Simulation::from_checkpoint(checkpoint);
+                println!("Loaded checkpoint from '{}'", path.display());
+            }
+            Err(e) => eprintln!("Failed to load checkpoint '{}': {}", path.display(), e),
+        }
+    }
+
+    fn switch_fluid_type(&mut self) {
+        if self.player.is_some() {
+            return;
+        }
+        let fluid_type = match self.simulation.config().fluid_type {
+            FluidType::Liquid => FluidType::Gas,
+            FluidType::Gas => FluidType::Liquid,
+        };
+        self.apply_input(InputEvent::SetFluidType(fluid_type));
+    }
+
+    fn reload_config(&mut self, frame_time: f32) {
+        match self.config_watcher.poll(frame_time) {
+            Some(Ok(config)) => {
+                self.panel.set_baseline(config.clone());
+                if self.player.is_some() {
+                    self.set_status("Config not applied during playback".to_owned(), RED);
+                } else {
+                    self.apply_input(InputEvent::ConfigChanged(Box::new(config)));
+                    self.set_status("Reloaded config".to_owned(), GREEN);
+                }
+            }
+            Some(Err(e)) => {
+                self.status = Some(StatusMessage {
+                    text: format!("Config not reloaded: {}", e),
+                    color: RED,
+                    remaining_time: None,
+                });
+            }
+            None => {}
+        }
+
+        if let Some(status) = &mut self.status
+            && let Some(remaining_time) = &mut status.remaining_time
+        {
+            *remaining_time -= frame_time;
+            if *remaining_time <= 0.0 {
+                self.status = None;
+            }
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        if !self.is_running {
+            std::process::exit(0);
+        }
+        self.reload_config(delta_time);
+
+        let Some(player) = &mut self.player else {
+            if !self.is_paused {
+                self.simulation.update(delta_time);
+            }
+            return;
+        };
+
+        // Pause toggles recorded while paused are due before the next step
+        if player.apply_due(&mut self.simulation) % 2 == 1 {
+            self.is_paused = !self.is_paused;
+        }
+        if !self.is_paused {
+            let is_paused = &mut self.is_paused;
+            self.simulation.update_with(delta_time, |simulation| {
+                if player.apply_due(simulation) % 2 == 1 {
+                    *is_paused = !*is_paused;
+                }
+                if *is_paused {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            });
+        }
+        if player.is_finished() {
+            self.player = None;
+            self.set_status("Playback finished".to_owned(), GREEN);
+        }
+    }
+
+    /// Collects the phase timings of the frame just drawn, which took
+    /// `frame_time` seconds in total and `render_time` to draw.
+    pub fn end_frame(&mut self, frame_time: f32, render_time: Duration) {
+        let mut timings = self.simulation.take_timings();
+        timings.add(Phase::Render, render_time);
+        self.profile_hud.record(frame_time, &timings);
+
+        self.frame_count += 1;
+        if let Some(trace) = &mut self.trace {
+            // Flushed every frame since closing the window ends the process
+            let result = trace
+                .write_row(
+                    self.frame_count,
+                    self.simulation.step_count(),
+                    self.simulation.fluid().particles.len(),
+                    &timings,
+                )
+                .and_then(|()| trace.flush());
+            if let Err(e) = result {
+                self.trace = None;
+                self.set_status(format!("Profile trace stopped: {}", e), RED);
+            }
+        }
+    }
+
+    fn camera(&self) -> Camera2D {
+        self.view.camera(self.simulation.boundary().domain_size())
+    }
+
+    pub fn render(&self) {
+        let camera = self.camera();
+        let fluid = self.simulation.fluid();
+        let config = self.simulation.config();
+        let colors = self
+            .render_mode
+            .draws_particles()
+            .then(|| ParticleColors::new(fluid, config));
+
+        set_camera(&camera);
+        render::draw_boundary(self.simulation.boundary());
+        if self.overlays.show_grid {
+            render::draw_grid(fluid);
+        }
+        if self.render_mode.draws_surface() {
+            let surface = Surface::extract(fluid, self.simulation.boundary(), config);
+            render::draw_surface(&surface, &config.surface);
+        }
+        if let Some(colors) = &colors {
+            render::draw_fluid(fluid, colors, self.overlays.show_ghosts);
+        }
+        self.overlays
+            .draw_world(fluid, self.simulation.boundary(), config, &camera);
+        self.draw_interaction_radius();
+
+        set_default_camera();
+        self.overlays.draw_screen(fluid, config, &camera);
+        if let Some(colors) = &colors {
+            render::draw_legend(colors, vec2(screen_width() - 10.0, screen_height() - 10.0));
+        }
+        self.draw_status();
+        self.draw_hud();
+        self.profile_hud.draw();
+    }
+
+    fn draw_hud(&self) {
+        let mut text = format!(
+            "Step: {}  Time: {:.3}s",
+            self.simulation.step_count(),
+            self.simulation.elapsed_time()
+        );
+        if self.is_paused {
+            text.push_str("  [PAUSED]");
+        }
+        if let Some(recorder) = &self.recorder {
+            text.push_str(&format!("  [REC {} events]", recorder.event_count()));
+        }
+        if let Some(player) = &self.player {
+            let (applied, total) = player.progress();
+            text.push_str(&format!("  [REPLAY {}/{}]", applied, total));
+        }
+        draw_text(&text, 10.0, screen_height() - 10.0, 20.0, WHITE);
+    }
+
+    fn draw_status(&self) {
+        if let Some(status) = &self.status {
+            for (index, line) in status.text.lines().enumerate() {
+                draw_text(line, 10.0, 20.0 + index as f32 * 20.0, 20.0, status.color);
+            }
+        }
+    }
+
+    pub fn draw_interaction_radius(&self) {
+        if let Some(point) = self.simulation.click_point() {
+            let radius = self.simulation.config().interaction_radius;
+            draw_circle_lines(point.x, point.y, radius, 1., GREEN);
+        }
+    }
+}
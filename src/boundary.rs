@@ -1,7 +1,9 @@
-use macroquad::prelude::*;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
 
 use crate::particle::Particle;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Boundary {
     pub pos: Vec2,
     pub width: f32,
@@ -10,13 +12,13 @@ pub struct Boundary {
 }
 
 impl Boundary {
-    pub fn new(damping: f32, grid_size: f32) -> Self {
+    pub fn new(damping: f32, grid_size: f32, domain_size: Vec2) -> Self {
         // Make width and height multiples of grid_size
-        let width = ((screen_width() / grid_size).floor() * grid_size) - grid_size * 2.;
-        let height = ((screen_height() / grid_size).floor() * grid_size) - grid_size * 2.;
+        let width = ((domain_size.x / grid_size).floor() * grid_size) - grid_size * 2.;
+        let height = ((domain_size.y / grid_size).floor() * grid_size) - grid_size * 2.;
         let pos = Vec2::new(
-            (screen_width() - width) / 2.0,
-            (screen_height() - height) / 2.0,
+            (domain_size.x - width) / 2.0,
+            (domain_size.y - height) / 2.0,
         );
 
         Self {
@@ -27,8 +29,16 @@ impl Boundary {
         }
     }
 
-    pub fn draw(&self) {
-        draw_rectangle_lines(self.pos.x, self.pos.y, self.width, self.height, 1., WHITE);
+    /// The size of the domain the boundary was centred in by `new`.
+    pub fn domain_size(&self) -> Vec2 {
+        Vec2::new(
+            self.width + self.pos.x * 2.0,
+            self.height + self.pos.y * 2.0,
+        )
+    }
+
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping;
     }
 
     pub fn check_collision(&self, particles: &mut [Particle]) {
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use crate::{
+    boundary::Boundary,
+    config::{Config, ConfigError},
+    fluid::Fluid,
+};
+
+/// Complete state of a `Simulation`, enough to resume it bit-for-bit.
+///
+/// The active per-fluid-type parameters are not stored, they are derived
+/// again from the `liquid` or `gas` table of `config` when resuming.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub config: Config,
+    pub boundary: Boundary,
+    pub fluid: Fluid,
+    pub accumulator: f32,
+    pub elapsed_time: f32,
+    #[serde(default)]
+    pub step_count: u64,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    Invalid(ConfigError),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "{}", e),
+            CheckpointError::Format(e) => write!(f, "invalid checkpoint: {}", e),
+            CheckpointError::Invalid(e) => write!(f, "checkpoint has an {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> Self {
+        CheckpointError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(e: serde_json::Error) -> Self {
+        CheckpointError::Format(e)
+    }
+}
+
+impl Checkpoint {
+    pub fn save(&self, path: &Path) -> Result<(), CheckpointError> {
+        let writer = BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Loads a checkpoint and validates its config, so that a hand-edited
+    /// or corrupted file is reported instead of crashing the solver.
+    pub fn load(path: &Path) -> Result<Self, CheckpointError> {
+        let reader = BufReader::new(fs::File::open(path)?);
+        let checkpoint: Self = serde_json::from_reader(reader)?;
+        checkpoint
+            .config
+            .validate()
+            .map_err(CheckpointError::Invalid)?;
+        Ok(checkpoint)
+    }
+}
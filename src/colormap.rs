@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, fluid::Fluid, physics::Physics};
+
+/// The per-particle quantity that particles are coloured by.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ColorBy {
+    Speed,
+    Density,
+    Pressure,
+    NearDensity,
+    Vorticity,
+    ParticleId,
+}
+
+impl ColorBy {
+    const ALL: [ColorBy; 6] = [
+        ColorBy::Speed,
+        ColorBy::Density,
+        ColorBy::Pressure,
+        ColorBy::NearDensity,
+        ColorBy::Vorticity,
+        ColorBy::ParticleId,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&c| c == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorBy::Speed => "Speed",
+            ColorBy::Density => "Density",
+            ColorBy::Pressure => "Pressure",
+            ColorBy::NearDensity => "Near density",
+            ColorBy::Vorticity => "Vorticity",
+            ColorBy::ParticleId => "Particle id",
+        }
+    }
+}
+
+/// Maps a normalised value in `[0, 1]` to a colour.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Palette {
+    /// Blue, cyan, yellow, red.
+    Classic,
+    Viridis,
+    Plasma,
+    Greyscale,
+}
+
+const CLASSIC_STOPS: [(f32, [u8; 3]); 4] = [
+    (0.0, [0, 0, 255]),
+    (0.2, [0, 255, 255]),
+    (0.5, [255, 255, 0]),
+    (1.0, [255, 0, 0]),
+];
+
+const VIRIDIS_STOPS: [(f32, [u8; 3]); 9] = [
+    (0.0, [68, 1, 84]),
+    (0.125, [71, 44, 122]),
+    (0.25, [59, 81, 139]),
+    (0.375, [44, 113, 142]),
+    (0.5, [33, 144, 141]),
+    (0.625, [39, 173, 129]),
+    (0.75, [92, 200, 99]),
+    (0.875, [170, 220, 50]),
+    (1.0, [253, 231, 37]),
+];
+
+const PLASMA_STOPS: [(f32, [u8; 3]); 9] = [
+    (0.0, [13, 8, 135]),
+    (0.125, [75, 3, 161]),
+    (0.25, [125, 3, 168]),
+    (0.375, [168, 34, 150]),
+    (0.5, [203, 70, 121]),
+    (0.625, [229, 107, 93]),
+    (0.75, [248, 148, 65]),
+    (0.875, [253, 195, 40]),
+    (1.0, [240, 249, 33]),
+];
+
+const GREYSCALE_STOPS: [(f32, [u8; 3]); 2] = [(0.0, [0, 0, 0]), (1.0, [255, 255, 255])];
+
+impl Palette {
+    const ALL: [Palette; 4] = [
+        Palette::Classic,
+        Palette::Viridis,
+        Palette::Plasma,
+        Palette::Greyscale,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&p| p == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Palette::Classic => "Classic",
+            Palette::Viridis => "Viridis",
+            Palette::Plasma => "Plasma",
+            Palette::Greyscale => "Greyscale",
+        }
+    }
+
+    /// The RGB colour for `t`, each channel in `[0, 1]`. `t` is clamped to
+    /// `[0, 1]`.
+    pub fn sample(self, t: f32) -> [f32; 3] {
+        let stops: &[(f32, [u8; 3])] = match self {
+            Palette::Classic => &CLASSIC_STOPS,
+            Palette::Viridis => &VIRIDIS_STOPS,
+            Palette::Plasma => &PLASMA_STOPS,
+            Palette::Greyscale => &GREYSCALE_STOPS,
+        };
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+
+        let upper = stops
+            .iter()
+            .position(|&(position, _)| position >= t)
+            .unwrap_or(stops.len() - 1)
+            .max(1);
+        let (start, start_color) = stops[upper - 1];
+        let (end, end_color) = stops[upper];
+        let s = (t - start) / (end - start);
+
+        let mut color = [0.0; 3];
+        for (channel, value) in color.iter_mut().enumerate() {
+            let from = start_color[channel] as f32 / 255.0;
+            let to = end_color[channel] as f32 / 255.0;
+            *value = from + (to - from) * s;
+        }
+        color
+    }
+}
+
+/// The values that the fluid's particles are coloured by, and the range that
+/// is mapped onto the palette, computed from `config.color_map`.
+pub struct ParticleColors {
+    pub color_by: ColorBy,
+    pub palette: Palette,
+    pub values: Vec<f32>,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl ParticleColors {
+    pub fn new(fluid: &Fluid, config: &Config) -> Self {
+        let settings = &config.color_map;
+        let particles = &fluid.particles;
+
+        let values: Vec<f32> = match settings.color_by {
+            ColorBy::Speed => particles.iter().map(|p| p.velocity.length()).collect(),
+            ColorBy::Density => particles.iter().map(|p| p.density).collect(),
+            ColorBy::NearDensity => particles.iter().map(|p| p.near_density).collect(),
+            ColorBy::Pressure => particles
+                .iter()
+                .map(|p| {
+                    Physics::density_to_pressure(
+                        p.density,
+                        p.near_density,
+                        config.target_density,
+                        config.pressure_multiplier,
+                        config.near_pressure_multiplier,
+                    )
+                    .0
+                })
+                .collect(),
+            ColorBy::Vorticity => fluid.vorticity(config),
+            ColorBy::ParticleId => (0..particles.len()).map(|index| index as f32).collect(),
+        };
+
+        let [mut min, mut max] = match settings.color_by {
+            ColorBy::Speed => settings.speed_range,
+            ColorBy::Density => settings.density_range,
+            ColorBy::Pressure => settings.pressure_range,
+            ColorBy::NearDensity => settings.near_density_range,
+            ColorBy::Vorticity => settings.vorticity_range,
+            ColorBy::ParticleId => [0.0, particles.len().saturating_sub(1) as f32],
+        };
+        if settings.auto_range {
+            let fluid_values = values
+                .iter()
+                .zip(particles)
+                .filter(|(value, particle)| !particle.is_ghost && value.is_finite())
+                .map(|(&value, _)| value);
+            let (auto_min, auto_max) = fluid_values
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                    (min.min(value), max.max(value))
+                });
+            if auto_min <= auto_max {
+                min = auto_min;
+                max = auto_max;
+            }
+        }
+
+        Self {
+            color_by: settings.color_by,
+            palette: settings.palette,
+            values,
+            min,
+            max,
+        }
+    }
+
+    /// Where `value` lies between `min` and `max`, in `[0, 1]`.
+    pub fn normalize(&self, value: f32) -> f32 {
+        if self.max > self.min {
+            ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// The RGB colour of the particle at `index`.
+    pub fn color(&self, index: usize) -> [f32; 3] {
+        self.palette.sample(self.normalize(self.values[index]))
+    }
+}
+
+/// Formats a legend range value, switching to scientific notation for very
+/// small or large magnitudes.
+pub fn format_legend_value(value: f32) -> String {
+    if value != 0.0 && !(0.01..100000.0).contains(&value.abs()) {
+        format!("{:.2e}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
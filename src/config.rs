@@ -1,8 +1,15 @@
 use clap::ValueEnum;
-use macroquad::prelude::*;
-use serde::Deserialize;
+use glam::Vec2;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+use crate::colormap::{ColorBy, Palette};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct SerializableVec2 {
     pub x: f32,
     pub y: f32,
@@ -14,27 +21,33 @@ impl From<SerializableVec2> for Vec2 {
     }
 }
 
+impl From<Vec2> for SerializableVec2 {
+    fn from(v: Vec2) -> Self {
+        Self { x: v.x, y: v.y }
+    }
+}
+
 #[allow(dead_code)]
-#[derive(Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
 pub enum FluidType {
     Gas,
     Liquid,
 }
 
 #[allow(dead_code)]
-#[derive(Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
 pub enum FluidSpawnMode {
     Grid,
     Flow,
 }
 
-#[derive(Clone, Copy, PartialEq, Deserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
 pub enum InteractionType {
     Pull,
     Push,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FluidTypeSpecifics {
     pub gravity: SerializableVec2,
     pub target_density: f32,
@@ -44,7 +57,139 @@ pub struct FluidTypeSpecifics {
     pub interaction_strength: f32,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl FluidTypeSpecifics {
+    pub fn liquid() -> Self {
+        Self {
+            gravity: SerializableVec2 { x: 0.0, y: 1.0 },
+            target_density: 5000.0,
+            pressure_multiplier: 750.0,
+            near_pressure_multiplier: 100.0,
+            viscosity_strength: 3.0,
+            interaction_strength: 2500.0,
+        }
+    }
+
+    pub fn gas() -> Self {
+        Self {
+            gravity: SerializableVec2 { x: 0.0, y: 0.0 },
+            target_density: 150.0,
+            pressure_multiplier: 150.0,
+            near_pressure_multiplier: 0.0,
+            viscosity_strength: 5.0,
+            interaction_strength: 5000.0,
+        }
+    }
+}
+
+/// How particles are coloured, see `colormap::ParticleColors`.
+///
+/// Each `*_range` is the `[min, max]` mapped onto the palette when
+/// `auto_range` is off. Particle ids always span the whole particle list.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorMapConfig {
+    pub color_by: ColorBy,
+    pub palette: Palette,
+    pub auto_range: bool,
+    pub speed_range: [f32; 2],
+    pub density_range: [f32; 2],
+    pub pressure_range: [f32; 2],
+    pub near_density_range: [f32; 2],
+    pub vorticity_range: [f32; 2],
+}
+
+impl Default for ColorMapConfig {
+    fn default() -> Self {
+        Self {
+            color_by: ColorBy::Speed,
+            palette: Palette::Classic,
+            auto_range: false,
+            speed_range: [0.0, 750.0],
+            density_range: [0.0, 10000.0],
+            pressure_range: [-1000000.0, 3000000.0],
+            near_density_range: [0.0, 10000.0],
+            vorticity_range: [-2000.0, 2000.0],
+        }
+    }
+}
+
+/// How the fluid surface is drawn in the surface render mode, see
+/// `surface::Surface`. Colours are RGBA with components in `[0, 1]`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SurfaceConfig {
+    /// Spacing of the density lattice in world units.
+    pub resolution: f32,
+    /// Density at the surface as a fraction of `target_density`.
+    pub threshold: f32,
+    pub fill_color: [f32; 4],
+    pub outline_color: [f32; 4],
+    pub outline_width: f32,
+}
+
+impl Default for SurfaceConfig {
+    fn default() -> Self {
+        Self {
+            resolution: 10.0,
+            threshold: 0.3,
+            fill_color: [0.15, 0.45, 0.9, 0.85],
+            outline_color: [0.75, 0.9, 1.0, 1.0],
+            outline_width: 2.0,
+        }
+    }
+}
+
+/// A `[liquid]` or `[gas]` table where every field is optional, so a partial
+/// table only overrides what it names and keeps the defaults for that type.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FluidTypeSpecificsOverrides {
+    gravity: Option<SerializableVec2>,
+    target_density: Option<f32>,
+    pressure_multiplier: Option<f32>,
+    near_pressure_multiplier: Option<f32>,
+    viscosity_strength: Option<f32>,
+    interaction_strength: Option<f32>,
+}
+
+impl FluidTypeSpecificsOverrides {
+    fn apply_to(self, defaults: FluidTypeSpecifics) -> FluidTypeSpecifics {
+        FluidTypeSpecifics {
+            gravity: self.gravity.unwrap_or(defaults.gravity),
+            target_density: self.target_density.unwrap_or(defaults.target_density),
+            pressure_multiplier: self
+                .pressure_multiplier
+                .unwrap_or(defaults.pressure_multiplier),
+            near_pressure_multiplier: self
+                .near_pressure_multiplier
+                .unwrap_or(defaults.near_pressure_multiplier),
+            viscosity_strength: self
+                .viscosity_strength
+                .unwrap_or(defaults.viscosity_strength),
+            interaction_strength: self
+                .interaction_strength
+                .unwrap_or(defaults.interaction_strength),
+        }
+    }
+}
+
+fn deserialize_liquid<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<FluidTypeSpecifics, D::Error> {
+    Ok(FluidTypeSpecificsOverrides::deserialize(deserializer)?
+        .apply_to(FluidTypeSpecifics::liquid()))
+}
+
+fn deserialize_gas<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<FluidTypeSpecifics, D::Error> {
+    Ok(FluidTypeSpecificsOverrides::deserialize(deserializer)?.apply_to(FluidTypeSpecifics::gas()))
+}
+
+/// Every field is optional in the config file and falls back to the value in
+/// `Config::default`, which `--print-default-config` prints in full.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub particle_radius: f32,
     pub particle_count: u32,
@@ -52,6 +197,11 @@ pub struct Config {
     pub mass: f32,
     pub smoothing_radius: f32,
     pub interaction_radius: f32,
+    pub domain_width: f32,
+    pub domain_height: f32,
+    pub fullscreen: bool,
+    pub window_width: u32,
+    pub window_height: u32,
 
     // Active parameters (populated by adapt_to_fluid_type)
     #[serde(skip)]
@@ -74,17 +224,84 @@ pub struct Config {
     pub fluid_spawn_mode: FluidSpawnMode,
     pub flow_spawn_rate: f32,
     pub flow_spawn_width: f32,
+    pub seed: u64,
+    pub time_step: f32,
+    pub substeps: u32,
+    pub max_steps_per_frame: u32,
+    pub time_scale: f32,
+    pub adaptive_time_step: bool,
+    pub cfl_factor: f32,
+    pub force_factor: f32,
+    pub min_time_step: f32,
+    pub max_time_step: f32,
+    #[serde(deserialize_with = "deserialize_liquid")]
     pub liquid: FluidTypeSpecifics,
+    #[serde(deserialize_with = "deserialize_gas")]
     pub gas: FluidTypeSpecifics,
+    pub color_map: ColorMapConfig,
+    pub surface: SurfaceConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut config = Self {
+            particle_radius: 3.0,
+            particle_count: 3000,
+            boundary_damping: 0.7,
+            mass: 1.0,
+            smoothing_radius: 40.0,
+            interaction_radius: 200.0,
+            domain_width: 1920.0,
+            domain_height: 1080.0,
+            fullscreen: false,
+            window_width: 1280,
+            window_height: 720,
+
+            gravity: Vec2::ZERO,
+            target_density: 0.0,
+            pressure_multiplier: 0.0,
+            near_pressure_multiplier: 0.0,
+            viscosity_strength: 0.0,
+            interaction_strength: 0.0,
+
+            target_ghost_spacing: 3.0,
+            start_ghost_spacing_multiplier: 2.6,
+            ghost_wall_start_percent: 0.6,
+            fluid_type: FluidType::Liquid,
+            fluid_spawn_mode: FluidSpawnMode::Grid,
+            flow_spawn_rate: 100.0,
+            flow_spawn_width: 120.0,
+            seed: 0,
+            time_step: 1.0 / 60.0,
+            substeps: 1,
+            max_steps_per_frame: 4,
+            time_scale: 1.0,
+            adaptive_time_step: false,
+            cfl_factor: 0.4,
+            force_factor: 0.25,
+            min_time_step: 0.0005,
+            max_time_step: 1.0 / 60.0,
+            liquid: FluidTypeSpecifics::liquid(),
+            gas: FluidTypeSpecifics::gas(),
+            color_map: ColorMapConfig::default(),
+            surface: SurfaceConfig::default(),
+        };
+        config.adapt_to_fluid_type();
+        config
+    }
 }
 
 impl Config {
     pub fn adapt_to_fluid_type(&mut self) {
         let specifics = match self.fluid_type {
-            FluidType::Liquid => &self.liquid,
-            FluidType::Gas => &self.gas,
+            FluidType::Liquid => self.liquid.clone(),
+            FluidType::Gas => self.gas.clone(),
         };
 
+        self.apply_specifics(&specifics);
+    }
+
+    pub fn apply_specifics(&mut self, specifics: &FluidTypeSpecifics) {
         self.gravity = specifics.gravity.into();
         self.target_density = specifics.target_density;
         self.pressure_multiplier = specifics.pressure_multiplier;
@@ -92,4 +309,827 @@ impl Config {
         self.viscosity_strength = specifics.viscosity_strength;
         self.interaction_strength = specifics.interaction_strength;
     }
+
+    /// Copies the parameters that can change while a simulation is running
+    /// from `other`, then re-applies the table for the current fluid type.
+    ///
+    /// Fields that only take effect when the fluid is spawned (particle size
+    /// and count, smoothing radius, ghost layout, fluid type, spawn mode and
+    /// seed) are left untouched. So are the colour map's quantity, palette and
+    /// auto range, which are chosen at runtime; only its ranges are copied.
+    pub fn apply_tunables(&mut self, other: &Config) {
+        self.boundary_damping = other.boundary_damping;
+        self.mass = other.mass;
+        self.interaction_radius = other.interaction_radius;
+        self.time_step = other.time_step;
+        self.substeps = other.substeps;
+        self.max_steps_per_frame = other.max_steps_per_frame;
+        self.time_scale = other.time_scale;
+        self.adaptive_time_step = other.adaptive_time_step;
+        self.cfl_factor = other.cfl_factor;
+        self.force_factor = other.force_factor;
+        self.min_time_step = other.min_time_step;
+        self.max_time_step = other.max_time_step;
+        self.liquid = other.liquid.clone();
+        self.gas = other.gas.clone();
+        self.color_map = ColorMapConfig {
+            color_by: self.color_map.color_by,
+            palette: self.color_map.palette,
+            auto_range: self.color_map.auto_range,
+            ..other.color_map.clone()
+        };
+        self.surface = other.surface.clone();
+        self.adapt_to_fluid_type();
+    }
+}
+
+/// A config field whose value is outside of its allowed range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub value: String,
+    pub expected: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} = {}: expected {}",
+            self.field, self.value, self.expected
+        )
+    }
+}
+
+/// A `key=value` override from the command line. Nested keys are separated by
+/// dots, e.g. `liquid.viscosity_strength=5`.
+#[derive(Debug, Clone)]
+pub struct ConfigOverride {
+    pub key: String,
+    pub value: toml::Value,
+}
+
+impl FromStr for ConfigOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, raw_value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got '{}'", s))?;
+        let key = key.trim();
+        if key.is_empty() || key.split('.').any(str::is_empty) {
+            return Err(format!("invalid key '{}'", key));
+        }
+
+        // Anything that is not a valid TOML value is taken as a bare string,
+        // so `fluid_type=Gas` works without quoting.
+        let raw_value = raw_value.trim();
+        let value = toml::from_str::<toml::Table>(&format!("value = {}", raw_value))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(raw_value.to_owned()));
+
+        Ok(Self {
+            key: key.to_owned(),
+            value,
+        })
+    }
+}
+
+impl ConfigOverride {
+    fn apply(&self, table: &mut toml::Table) -> Result<(), ConfigError> {
+        let mut parts: Vec<&str> = self.key.split('.').collect();
+        let field = parts.pop().expect("key has at least one part");
+
+        let mut current = table;
+        for part in parts {
+            let entry = current
+                .entry(part)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            current = match entry {
+                toml::Value::Table(nested) => nested,
+                _ => {
+                    return Err(ConfigError::Override {
+                        key: self.key.clone(),
+                        message: format!("'{}' is not a table", part),
+                    });
+                }
+            };
+        }
+        current.insert(field.to_owned(), self.value.clone());
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Override {
+        key: String,
+        message: String,
+    },
+    Invalid(Vec<FieldError>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => {
+                write!(
+                    f,
+                    "failed to read config file '{}': {}",
+                    path.display(),
+                    source
+                )
+            }
+            ConfigError::Parse { path, source } => {
+                write!(
+                    f,
+                    "failed to parse config file '{}': {}",
+                    path.display(),
+                    source
+                )
+            }
+            ConfigError::Override { key, message } => {
+                write!(f, "failed to apply override '{}': {}", key, message)
+            }
+            ConfigError::Invalid(errors) => {
+                write!(f, "invalid configuration:")?;
+                for error in errors {
+                    write!(f, "\n  - {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+            ConfigError::Override { .. } | ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads and parses a config file without validating it, so that command
+    /// line overrides can be applied before `validate`.
+    ///
+    /// `overrides` are applied to the parsed TOML before it is turned into a
+    /// `Config`, so they go through the same defaults and type checks as
+    /// values from the file. The file is checked on its own first, and then
+    /// after each override, so that errors name the override that caused
+    /// them rather than blaming the file.
+    pub fn from_file(path: &Path, overrides: &[ConfigOverride]) -> Result<Self, ConfigError> {
+        let config_str = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let parse_error = |source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let mut table: toml::Table = toml::from_str(&config_str).map_err(parse_error)?;
+        let mut config =
+            Config::deserialize(toml::Value::Table(table.clone())).map_err(parse_error)?;
+        for config_override in overrides {
+            config_override.apply(&mut table)?;
+            config = Config::deserialize(toml::Value::Table(table.clone())).map_err(|source| {
+                ConfigError::Override {
+                    key: config_override.key.clone(),
+                    message: source.message().to_owned(),
+                }
+            })?;
+        }
+        Ok(config)
+    }
+
+    /// The full default configuration as TOML, for `--print-default-config`.
+    pub fn default_toml() -> String {
+        let mut value =
+            toml::Value::try_from(Self::default()).expect("default config is valid TOML");
+        shorten_floats(&mut value);
+        let toml = toml::to_string_pretty(&value).expect("default config is valid TOML");
+        with_field_docs(&toml)
+    }
+
+    /// Checks every field against its allowed range and reports all invalid
+    /// fields at once rather than stopping at the first.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut validator = Validator::default();
+
+        validator.positive("particle_radius", self.particle_radius);
+        validator.in_range("boundary_damping", self.boundary_damping, 0.0, 1.0);
+        validator.positive("mass", self.mass);
+        validator.positive("smoothing_radius", self.smoothing_radius);
+        validator.non_negative("interaction_radius", self.interaction_radius);
+        // The boundary is inset by one cell on each side and needs at least
+        // one more cell of room inside it.
+        let min_domain_size = self.smoothing_radius * 3.0;
+        for (field, value) in [
+            ("domain_width", self.domain_width),
+            ("domain_height", self.domain_height),
+        ] {
+            validator.check(
+                field,
+                value,
+                value >= min_domain_size,
+                format!(
+                    "a value of at least 3 * smoothing_radius ({})",
+                    min_domain_size
+                ),
+            );
+        }
+        validator.check(
+            "window_width",
+            self.window_width,
+            self.window_width >= 1,
+            "a value of at least 1",
+        );
+        validator.check(
+            "window_height",
+            self.window_height,
+            self.window_height >= 1,
+            "a value of at least 1",
+        );
+
+        validator.positive("target_ghost_spacing", self.target_ghost_spacing);
+        validator.positive(
+            "start_ghost_spacing_multiplier",
+            self.start_ghost_spacing_multiplier,
+        );
+        validator.check(
+            "ghost_wall_start_percent",
+            self.ghost_wall_start_percent,
+            (0.0..1.0).contains(&self.ghost_wall_start_percent),
+            "a value in [0, 1)",
+        );
+        let is_gas_flow =
+            self.fluid_type == FluidType::Gas && self.fluid_spawn_mode == FluidSpawnMode::Flow;
+        validator.check(
+            "fluid_spawn_mode",
+            format!("\"{:?}\"", self.fluid_spawn_mode),
+            !is_gas_flow,
+            "\"Grid\", since Flow spawning is not supported for gas",
+        );
+        validator.non_negative("flow_spawn_rate", self.flow_spawn_rate);
+        validator.non_negative("flow_spawn_width", self.flow_spawn_width);
+
+        validator.positive("time_step", self.time_step);
+        validator.check(
+            "substeps",
+            self.substeps,
+            self.substeps >= 1,
+            "a value of at least 1",
+        );
+        validator.check(
+            "max_steps_per_frame",
+            self.max_steps_per_frame,
+            self.max_steps_per_frame >= 1,
+            "a value of at least 1",
+        );
+        validator.non_negative("time_scale", self.time_scale);
+        validator.positive("cfl_factor", self.cfl_factor);
+        validator.positive("force_factor", self.force_factor);
+        validator.positive("min_time_step", self.min_time_step);
+        validator.check(
+            "max_time_step",
+            self.max_time_step,
+            self.max_time_step >= self.min_time_step,
+            format!("a value of at least min_time_step ({})", self.min_time_step),
+        );
+
+        validator.specifics("liquid", &self.liquid);
+        validator.specifics("gas", &self.gas);
+        validator.color_map(&self.color_map);
+        validator.surface(&self.surface);
+
+        if validator.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(validator.errors))
+        }
+    }
+}
+
+/// Comments for `default_toml`, keyed by field or table name. Names are
+/// unique across tables, and the `liquid` and `gas` fields share theirs.
+const FIELD_DOCS: &[(&str, &str)] = &[
+    ("particle_radius", "Radius of a particle in world units"),
+    (
+        "particle_count",
+        "Number of fluid particles, spawned at once or over time",
+    ),
+    (
+        "boundary_damping",
+        "Fraction of the speed kept when bouncing off a wall, in [0, 1]",
+    ),
+    ("mass", "Mass of a particle"),
+    (
+        "smoothing_radius",
+        "Interaction range of the SPH kernels, also the grid cell size",
+    ),
+    ("interaction_radius", "Radius of the mouse pull and push"),
+    (
+        "domain_width",
+        "Simulation domain in world units, independent of the window",
+    ),
+    (
+        "domain_height",
+        "Simulation domain in world units, independent of the window",
+    ),
+    ("fullscreen", "Start in fullscreen instead of a window"),
+    (
+        "window_width",
+        "Initial window size in pixels, the domain is scaled to fit it",
+    ),
+    (
+        "window_height",
+        "Initial window size in pixels, the domain is scaled to fit it",
+    ),
+    (
+        "target_ghost_spacing",
+        "Ghost particle spacing along the walls, in particle radii",
+    ),
+    (
+        "start_ghost_spacing_multiplier",
+        "How much sparser the ghosts are where the side walls start",
+    ),
+    (
+        "ghost_wall_start_percent",
+        "Where the side wall ghosts start, in [0, 1): 0.0 = top edge, values towards 1.0 move the start down",
+    ),
+    (
+        "fluid_type",
+        "\"Liquid\" or \"Gas\", selects the [liquid] or [gas] table",
+    ),
+    (
+        "fluid_spawn_mode",
+        "\"Grid\" spawns every particle at once, \"Flow\" (liquid only) pours them in",
+    ),
+    ("flow_spawn_rate", "Particles per second in flow mode"),
+    ("flow_spawn_width", "Width of the spawn area in flow mode"),
+    (
+        "seed",
+        "Seed for the random number generator, runs with the same seed are identical",
+    ),
+    (
+        "time_step",
+        "Fixed simulation step in seconds, independent of the frame rate",
+    ),
+    (
+        "substeps",
+        "Solver iterations each fixed step is split into",
+    ),
+    (
+        "max_steps_per_frame",
+        "Steps beyond this in a single frame are dropped",
+    ),
+    (
+        "time_scale",
+        "Multiplier on real time, below 1 for slow motion",
+    ),
+    (
+        "adaptive_time_step",
+        "Split substeps further when particles move fast",
+    ),
+    (
+        "cfl_factor",
+        "Max fraction of smoothing_radius a particle may travel per adaptive step",
+    ),
+    (
+        "force_factor",
+        "Scales the acceleration limit sqrt(smoothing_radius / max_acceleration)",
+    ),
+    ("min_time_step", "Shortest adaptive step in seconds"),
+    ("max_time_step", "Longest adaptive step in seconds"),
+    ("liquid", "Parameters used while the fluid type is Liquid"),
+    ("gas", "Parameters used while the fluid type is Gas"),
+    (
+        "gravity",
+        "Gravity in thousands of world units per second squared",
+    ),
+    ("target_density", "Rest density the pressure pushes towards"),
+    ("pressure_multiplier", "Stiffness of the pressure response"),
+    (
+        "near_pressure_multiplier",
+        "Stiffness of the short-range repulsion that keeps particles apart",
+    ),
+    (
+        "viscosity_strength",
+        "How strongly neighbours' velocities are evened out",
+    ),
+    (
+        "interaction_strength",
+        "Strength of the mouse pull and push",
+    ),
+    (
+        "color_map",
+        "Particle colouring, cycled at runtime with C (quantity) and P (palette)",
+    ),
+    (
+        "color_by",
+        "\"Speed\", \"Density\", \"Pressure\", \"NearDensity\", \"Vorticity\" or \"ParticleId\"",
+    ),
+    (
+        "palette",
+        "\"Classic\", \"Viridis\", \"Plasma\" or \"Greyscale\"",
+    ),
+    (
+        "auto_range",
+        "Fit the range to the current values instead of using the ranges below",
+    ),
+    ("speed_range", "[min, max] mapped onto the palette"),
+    ("density_range", "[min, max] mapped onto the palette"),
+    ("pressure_range", "[min, max] mapped onto the palette"),
+    ("near_density_range", "[min, max] mapped onto the palette"),
+    ("vorticity_range", "[min, max] mapped onto the palette"),
+    (
+        "surface",
+        "Fluid surface in the surface render mode, switched with M",
+    ),
+    (
+        "resolution",
+        "Spacing of the density lattice the surface is traced on",
+    ),
+    (
+        "threshold",
+        "Density at the surface as a fraction of target_density",
+    ),
+    ("fill_color", "RGBA, components in [0, 1]"),
+    ("outline_color", "RGBA, components in [0, 1]"),
+    ("outline_width", "Outline width in world units"),
+];
+
+/// Puts the comment from `FIELD_DOCS` above every key and table header.
+fn with_field_docs(toml: &str) -> String {
+    let mut output = String::with_capacity(toml.len() * 2);
+    for line in toml.lines() {
+        let name = match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            Some(header) => header.rsplit('.').next(),
+            // Continuation lines of multi-line arrays are indented
+            None if !line.starts_with(' ') => line.split_once(" = ").map(|(key, _)| key),
+            None => None,
+        };
+        let doc = name.and_then(|name| {
+            FIELD_DOCS
+                .iter()
+                .find(|(field, _)| *field == name)
+                .map(|(_, doc)| doc)
+        });
+        if let Some(doc) = doc {
+            output.push_str("# ");
+            output.push_str(doc);
+            output.push('\n');
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    output
+}
+
+/// TOML stores floats as f64, so `0.7_f32` would otherwise be written as
+/// `0.699999988079071`. Rounds every float to its shortest f32 form.
+fn shorten_floats(value: &mut toml::Value) {
+    match value {
+        toml::Value::Float(f) => *f = (*f as f32).to_string().parse().unwrap_or(*f),
+        toml::Value::Table(table) => table.iter_mut().for_each(|(_, v)| shorten_floats(v)),
+        toml::Value::Array(array) => array.iter_mut().for_each(shorten_floats),
+        _ => {}
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    fn check(
+        &mut self,
+        field: &str,
+        value: impl fmt::Display,
+        is_valid: bool,
+        expected: impl Into<String>,
+    ) {
+        if !is_valid {
+            self.errors.push(FieldError {
+                field: field.to_owned(),
+                value: value.to_string(),
+                expected: expected.into(),
+            });
+        }
+    }
+
+    // The comparisons below are written so that NaN is rejected as well.
+    fn positive(&mut self, field: &str, value: f32) {
+        self.check(
+            field,
+            value,
+            value > 0.0 && value.is_finite(),
+            "a value greater than 0",
+        );
+    }
+
+    fn non_negative(&mut self, field: &str, value: f32) {
+        self.check(
+            field,
+            value,
+            value >= 0.0 && value.is_finite(),
+            "a value of at least 0",
+        );
+    }
+
+    fn in_range(&mut self, field: &str, value: f32, min: f32, max: f32) {
+        self.check(
+            field,
+            value,
+            (min..=max).contains(&value),
+            format!("a value in [{}, {}]", min, max),
+        );
+    }
+
+    fn range(&mut self, field: &str, range: [f32; 2]) {
+        let [min, max] = range;
+        self.check(
+            field,
+            format!("[{}, {}]", min, max),
+            min < max && min.is_finite() && max.is_finite(),
+            "a finite [min, max] with min < max",
+        );
+    }
+
+    fn color_map(&mut self, color_map: &ColorMapConfig) {
+        self.range("color_map.speed_range", color_map.speed_range);
+        self.range("color_map.density_range", color_map.density_range);
+        self.range("color_map.pressure_range", color_map.pressure_range);
+        self.range("color_map.near_density_range", color_map.near_density_range);
+        self.range("color_map.vorticity_range", color_map.vorticity_range);
+    }
+
+    fn color(&mut self, field: &str, color: [f32; 4]) {
+        self.check(
+            field,
+            format!("{:?}", color),
+            color.iter().all(|c| (0.0..=1.0).contains(c)),
+            "components in [0, 1]",
+        );
+    }
+
+    fn surface(&mut self, surface: &SurfaceConfig) {
+        self.positive("surface.resolution", surface.resolution);
+        self.positive("surface.threshold", surface.threshold);
+        self.color("surface.fill_color", surface.fill_color);
+        self.color("surface.outline_color", surface.outline_color);
+        self.non_negative("surface.outline_width", surface.outline_width);
+    }
+
+    fn specifics(&mut self, table: &str, specifics: &FluidTypeSpecifics) {
+        let field = |name: &str| format!("{}.{}", table, name);
+
+        self.check(
+            &field("gravity"),
+            format!(
+                "{{ x = {}, y = {} }}",
+                specifics.gravity.x, specifics.gravity.y
+            ),
+            specifics.gravity.x.is_finite() && specifics.gravity.y.is_finite(),
+            "finite components",
+        );
+        self.positive(&field("target_density"), specifics.target_density);
+        self.non_negative(&field("pressure_multiplier"), specifics.pressure_multiplier);
+        self.non_negative(
+            &field("near_pressure_multiplier"),
+            specifics.near_pressure_multiplier,
+        );
+        self.non_negative(&field("viscosity_strength"), specifics.viscosity_strength);
+        self.non_negative(
+            &field("interaction_strength"),
+            specifics.interaction_strength,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Loads a config file with `contents` and `overrides` applied in order.
+    fn load(contents: &str, overrides: &[&str]) -> Result<Config, ConfigError> {
+        // Tests run in parallel, so each load gets a file of its own
+        static FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "{}-config-{}.toml",
+            std::process::id(),
+            FILE_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&path, contents).expect("config file is written");
+        let overrides: Vec<ConfigOverride> = overrides
+            .iter()
+            .map(|s| s.parse().expect("override parses"))
+            .collect();
+        let result = Config::from_file(&path, &overrides);
+        fs::remove_file(&path).expect("config file is removed");
+        result
+    }
+
+    /// The key and message of an override error.
+    fn override_error(result: Result<Config, ConfigError>) -> (String, String) {
+        match result {
+            Err(ConfigError::Override { key, message }) => (key, message),
+            other => panic!("expected an override error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn nested_override_sets_a_table_value() {
+        let config = load("", &["liquid.gravity={x=0,y=2}"]).expect("config loads");
+        assert_eq!(config.liquid.gravity, SerializableVec2 { x: 0.0, y: 2.0 });
+        assert_eq!(config.gas, Config::default().gas);
+    }
+
+    #[test]
+    fn override_value_falls_back_to_a_bare_string() {
+        let bare: ConfigOverride = "fluid_type=Gas".parse().expect("override parses");
+        assert_eq!(bare.value, toml::Value::String("Gas".to_owned()));
+        let quoted: ConfigOverride = "fluid_type=\"Gas\"".parse().expect("override parses");
+        assert_eq!(quoted.value, bare.value);
+
+        let config = load("", &["fluid_type=Gas"]).expect("config loads");
+        assert_eq!(config.fluid_type, FluidType::Gas);
+    }
+
+    #[test]
+    fn override_through_a_non_table_value_fails() {
+        let (key, message) =
+            override_error(load("particle_count = 100\n", &["particle_count.x=1"]));
+        assert_eq!(key, "particle_count.x");
+        assert_eq!(message, "'particle_count' is not a table");
+
+        // Without the value in the file, the table is created and rejected
+        // when the config is deserialized
+        let (key, message) = override_error(load("", &["particle_count.x=1"]));
+        assert_eq!(key, "particle_count.x");
+        assert!(message.contains("expected u32"), "{}", message);
+    }
+
+    #[test]
+    fn override_keys_must_not_have_empty_segments() {
+        for s in ["=1", ".mass=1", "mass.=1", "liquid..mass=1", " =1"] {
+            assert!(s.parse::<ConfigOverride>().is_err(), "'{}' parses", s);
+        }
+        assert!("mass".parse::<ConfigOverride>().is_err());
+    }
+
+    #[test]
+    fn override_error_names_the_failing_override() {
+        let result = load("", &["mass=2", "particle_count=many", "seed=1"]);
+        let message = result.as_ref().unwrap_err().to_string();
+        assert!(
+            message.starts_with("failed to apply override 'particle_count': "),
+            "{}",
+            message
+        );
+        assert_eq!(override_error(result).0, "particle_count");
+
+        let (key, _) = override_error(load("", &["liquid.viscosity=1"]));
+        assert_eq!(key, "liquid.viscosity");
+    }
+
+    #[test]
+    fn later_overrides_win() {
+        let config = load("mass = 1.0\n", &["mass=2", "mass=3"]).expect("config loads");
+        assert_eq!(config.mass, 3.0);
+
+        let config =
+            load("", &["liquid.gravity={x=1,y=1}", "liquid.gravity.y=5"]).expect("config loads");
+        assert_eq!(config.liquid.gravity, SerializableVec2 { x: 1.0, y: 5.0 });
+    }
+
+    /// The fields `config` is rejected for, in the order they are listed.
+    fn invalid_fields(config: &Config) -> Vec<String> {
+        match config.validate() {
+            Err(ConfigError::Invalid(errors)) => {
+                errors.into_iter().map(|error| error.field).collect()
+            }
+            other => panic!("expected an invalid config, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        Config::default()
+            .validate()
+            .expect("default config is valid");
+    }
+
+    #[test]
+    fn every_invalid_field_is_listed() {
+        let mut config = Config {
+            mass: -1.0,
+            time_step: 0.0,
+            ..Config::default()
+        };
+        config.liquid.target_density = 0.0;
+        config.color_map.speed_range = [1.0, 1.0];
+
+        assert_eq!(
+            invalid_fields(&config),
+            [
+                "mass",
+                "time_step",
+                "liquid.target_density",
+                "color_map.speed_range"
+            ]
+        );
+        let message = config.validate().unwrap_err().to_string();
+        assert_eq!(message.lines().count(), 5);
+        assert!(message.contains("  - mass = -1: expected a value greater than 0"));
+    }
+
+    #[test]
+    fn nan_and_infinite_values_are_rejected() {
+        let mut config = Config {
+            particle_radius: f32::NAN,
+            boundary_damping: f32::NAN,
+            interaction_radius: f32::INFINITY,
+            max_time_step: f32::NAN,
+            ..Config::default()
+        };
+        config.gas.gravity.x = f32::NEG_INFINITY;
+
+        assert_eq!(
+            invalid_fields(&config),
+            [
+                "particle_radius",
+                "boundary_damping",
+                "interaction_radius",
+                "max_time_step",
+                "gas.gravity"
+            ]
+        );
+    }
+
+    #[test]
+    fn ghost_wall_start_percent_must_be_below_one() {
+        let with_start = |ghost_wall_start_percent| Config {
+            ghost_wall_start_percent,
+            ..Config::default()
+        };
+        assert!(with_start(0.0).validate().is_ok());
+        assert!(with_start(0.99).validate().is_ok());
+        assert_eq!(
+            invalid_fields(&with_start(1.0)),
+            ["ghost_wall_start_percent"]
+        );
+        assert_eq!(
+            invalid_fields(&with_start(-0.1)),
+            ["ghost_wall_start_percent"]
+        );
+    }
+
+    #[test]
+    fn domain_must_fit_three_smoothing_radii() {
+        let config = Config {
+            smoothing_radius: 100.0,
+            domain_width: 299.0,
+            domain_height: 300.0,
+            ..Config::default()
+        };
+        assert_eq!(invalid_fields(&config), ["domain_width"]);
+
+        let config = Config {
+            domain_width: 300.0,
+            ..config
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn gas_cannot_use_flow_spawning() {
+        let config = Config {
+            fluid_type: FluidType::Gas,
+            fluid_spawn_mode: FluidSpawnMode::Flow,
+            ..Config::default()
+        };
+        assert_eq!(invalid_fields(&config), ["fluid_spawn_mode"]);
+
+        let config = Config {
+            fluid_type: FluidType::Liquid,
+            ..config
+        };
+        assert!(config.validate().is_ok());
+    }
 }
@@ -0,0 +1,53 @@
+use fluid_simulation::config::{Config, ConfigError, ConfigOverride};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const POLL_INTERVAL: f32 = 0.5;
+
+/// Polls the config file's modification time and re-parses it when it changes.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    overrides: Vec<ConfigOverride>,
+    last_modified: Option<SystemTime>,
+    poll_timer: f32,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf, overrides: Vec<ConfigOverride>) -> Self {
+        let last_modified = modified_time(&path);
+        Self {
+            path,
+            overrides,
+            last_modified,
+            poll_timer: 0.0,
+        }
+    }
+
+    /// Returns the newly parsed and validated config if the file changed since
+    /// the last poll. `--set` overrides are re-applied on every reload.
+    pub fn poll(&mut self, delta_time: f32) -> Option<Result<Config, ConfigError>> {
+        self.poll_timer += delta_time;
+        if self.poll_timer < POLL_INTERVAL {
+            return None;
+        }
+        self.poll_timer = 0.0;
+
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.last_modified {
+            return None;
+        }
+        self.last_modified = modified;
+
+        Some(
+            Config::from_file(&self.path, &self.overrides).and_then(|config| {
+                config.validate()?;
+                Ok(config)
+            }),
+        )
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
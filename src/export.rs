@@ -0,0 +1,161 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::{config::Config, particle::Particle, physics::Physics};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Vtk,
+    Ply,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Vtk => "vtk",
+            ExportFormat::Ply => "ply",
+        }
+    }
+}
+
+/// Per-particle values written to every export format.
+struct FrameRow {
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+    density: f32,
+    near_density: f32,
+    pressure: f32,
+}
+
+/// Writes the fluid particles (ghosts excluded) to `path` in the given format.
+pub fn write_frame(
+    path: &Path,
+    format: ExportFormat,
+    particles: &[Particle],
+    config: &Config,
+) -> io::Result<()> {
+    let rows: Vec<FrameRow> = particles
+        .iter()
+        .filter(|p| !p.is_ghost)
+        .map(|p| {
+            let (pressure, _) = Physics::density_to_pressure(
+                p.density,
+                p.near_density,
+                config.target_density,
+                config.pressure_multiplier,
+                config.near_pressure_multiplier,
+            );
+            FrameRow {
+                x: p.position.x,
+                y: p.position.y,
+                vx: p.velocity.x,
+                vy: p.velocity.y,
+                density: p.density,
+                near_density: p.near_density,
+                pressure,
+            }
+        })
+        .collect();
+
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    match format {
+        ExportFormat::Csv => write_csv(&mut writer, &rows)?,
+        ExportFormat::Vtk => write_vtk(&mut writer, &rows)?,
+        ExportFormat::Ply => write_ply(&mut writer, &rows)?,
+    }
+    writer.flush()
+}
+
+fn write_csv(writer: &mut impl Write, rows: &[FrameRow]) -> io::Result<()> {
+    writeln!(writer, "x,y,vx,vy,density,near_density,pressure")?;
+    for row in rows {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            row.x, row.y, row.vx, row.vy, row.density, row.near_density, row.pressure
+        )?;
+    }
+    Ok(())
+}
+
+/// Legacy ASCII VTK polydata, one vertex cell per particle so ParaView
+/// renders the points without needing a glyph filter.
+fn write_vtk(writer: &mut impl Write, rows: &[FrameRow]) -> io::Result<()> {
+    let count = rows.len();
+    writeln!(writer, "# vtk DataFile Version 3.0")?;
+    writeln!(writer, "Fluid simulation frame")?;
+    writeln!(writer, "ASCII")?;
+    writeln!(writer, "DATASET POLYDATA")?;
+
+    writeln!(writer, "POINTS {} float", count)?;
+    for row in rows {
+        writeln!(writer, "{} {} 0", row.x, row.y)?;
+    }
+
+    writeln!(writer, "VERTICES {} {}", count, count * 2)?;
+    for index in 0..count {
+        writeln!(writer, "1 {}", index)?;
+    }
+
+    writeln!(writer, "POINT_DATA {}", count)?;
+    writeln!(writer, "VECTORS velocity float")?;
+    for row in rows {
+        writeln!(writer, "{} {} 0", row.vx, row.vy)?;
+    }
+
+    write_vtk_scalars(writer, "density", rows.iter().map(|row| row.density))?;
+    write_vtk_scalars(
+        writer,
+        "near_density",
+        rows.iter().map(|row| row.near_density),
+    )?;
+    write_vtk_scalars(writer, "pressure", rows.iter().map(|row| row.pressure))?;
+    Ok(())
+}
+
+fn write_vtk_scalars(
+    writer: &mut impl Write,
+    name: &str,
+    values: impl Iterator<Item = f32>,
+) -> io::Result<()> {
+    writeln!(writer, "SCALARS {} float 1", name)?;
+    writeln!(writer, "LOOKUP_TABLE default")?;
+    for value in values {
+        writeln!(writer, "{}", value)?;
+    }
+    Ok(())
+}
+
+fn write_ply(writer: &mut impl Write, rows: &[FrameRow]) -> io::Result<()> {
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format ascii 1.0")?;
+    writeln!(writer, "element vertex {}", rows.len())?;
+    for property in [
+        "x",
+        "y",
+        "z",
+        "vx",
+        "vy",
+        "density",
+        "near_density",
+        "pressure",
+    ] {
+        writeln!(writer, "property float {}", property)?;
+    }
+    writeln!(writer, "end_header")?;
+    for row in rows {
+        writeln!(
+            writer,
+            "{} {} 0 {} {} {} {} {}",
+            row.x, row.y, row.vx, row.vy, row.density, row.near_density, row.pressure
+        )?;
+    }
+    Ok(())
+}
@@ -1,5 +1,6 @@
-use macroquad::prelude::*;
+use glam::Vec2;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     boundary::Boundary,
@@ -7,10 +8,15 @@ use crate::{
     grid::GridCell,
     particle::Particle,
     physics::Physics,
+    profiler::{Phase, PhaseTimings},
+    rng::Rng,
+    simulation::DISTANCE_ZOOM,
     spawner::{ParticleSpawner, spawn_particles_grid},
 };
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Fluid {
+    #[serde(skip)]
     pub grid: Vec<GridCell>,
     pub particles: Vec<Particle>,
     grid_cols: usize,
@@ -18,6 +24,7 @@ pub struct Fluid {
     grid_size: f32,
     particle_spawner: Option<ParticleSpawner>,
     world_grid_origin: Vec2,
+    rng: Rng,
 }
 
 impl Fluid {
@@ -46,120 +53,24 @@ impl Fluid {
         }
 
         let grid_size = config.smoothing_radius;
-        let mut world_grid_origin = boundary.pos;
-
-        let base_grid_cols = (boundary.width / grid_size).ceil().max(1.0) as usize;
-        let base_grid_rows = (boundary.height / grid_size).ceil().max(1.0) as usize;
-
-        let mut current_grid_cols = base_grid_cols;
-        let mut current_grid_rows = base_grid_rows;
+        let (world_grid_origin, current_grid_cols, current_grid_rows) =
+            Self::grid_layout(config, boundary);
 
         if config.fluid_type == FluidType::Liquid {
-            let extended_grid_cols = base_grid_cols + 2;
-            let extended_grid_rows = base_grid_rows + 2;
-
-            world_grid_origin = boundary.pos - Vec2::new(grid_size, grid_size);
-            current_grid_cols = extended_grid_cols;
-            current_grid_rows = extended_grid_rows;
-
-            let ghost_layer_origin_x = world_grid_origin.x;
-            let ghost_layer_origin_y = world_grid_origin.y;
-
-            // Ghost particles
-            let mut ghost_particles_vec: Vec<Particle> = Vec::new();
-            let extended_grid_rows_f = extended_grid_rows as f32;
-
-            for r_idx in 0..extended_grid_rows {
-                for c_idx in 0..extended_grid_cols {
-                    if !(r_idx == 0
-                        || r_idx == extended_grid_rows - 1
-                        || c_idx == 0
-                        || c_idx == extended_grid_cols - 1)
-                    {
-                        continue;
-                    }
-
-                    let is_on_top_boundary = r_idx == 0;
-                    if is_on_top_boundary {
-                        continue;
-                    }
-
-                    let is_on_left_boundary = c_idx == 0;
-                    let is_on_right_boundary = c_idx == extended_grid_cols - 1;
-                    let is_on_bottom_boundary = r_idx == extended_grid_rows - 1;
-
-                    if is_on_left_boundary || is_on_right_boundary {
-                        let norm_y_in_col = (r_idx as f32) / (extended_grid_rows_f - 1.0);
-                        if norm_y_in_col < config.ghost_wall_start_percent {
-                            continue;
-                        }
-                    }
-
-                    let spacing_factor = {
-                        if is_on_bottom_boundary {
-                            1.0
-                        } else if is_on_left_boundary || is_on_right_boundary {
-                            let norm_y_in_col = (r_idx as f32) / (extended_grid_rows_f - 1.0);
-                            let range_norm_y = (norm_y_in_col - config.ghost_wall_start_percent)
-                                / (1.0 - config.ghost_wall_start_percent);
-                            let interp_t = range_norm_y.max(0.0).min(1.0);
-                            config.start_ghost_spacing_multiplier * (1.0 - interp_t)
-                                + 1.0 * interp_t
-                        } else {
-                            1.0
-                        }
-                    };
-
-                    let current_spacing =
-                        config.particle_radius * config.target_ghost_spacing * spacing_factor;
-
-                    if current_spacing <= 1e-3 {
-                        continue;
-                    }
-
-                    let count_x = (grid_size / current_spacing).floor().max(1.0) as u32;
-                    let count_y = (grid_size / current_spacing).floor().max(1.0) as u32;
-
-                    let margin_x = (grid_size - (count_x as f32 * current_spacing)) / 2.0;
-                    let margin_y = (grid_size - (count_y as f32 * current_spacing)) / 2.0;
-
-                    let cell_origin_x = ghost_layer_origin_x + c_idx as f32 * grid_size;
-                    let cell_origin_y = ghost_layer_origin_y + r_idx as f32 * grid_size;
-
-                    for i in 0..count_y {
-                        for j in 0..count_x {
-                            let px = cell_origin_x
-                                + margin_x
-                                + (j as f32 * current_spacing)
-                                + current_spacing * 0.5;
-                            let py = cell_origin_y
-                                + margin_y
-                                + (i as f32 * current_spacing)
-                                + current_spacing * 0.5;
-                            ghost_particles_vec.push(Particle::new(
-                                Vec2::new(px, py),
-                                config.particle_radius,
-                                true,
-                            ));
-                        }
-                    }
-                }
-            }
-            particles.extend(ghost_particles_vec);
+            particles.extend(Self::spawn_ghost_particles(
+                config,
+                world_grid_origin,
+                current_grid_cols,
+                current_grid_rows,
+            ));
         }
 
-        let mut grid: Vec<GridCell> = Vec::with_capacity(current_grid_cols * current_grid_rows);
-        for i in 0..current_grid_rows {
-            for j in 0..current_grid_cols {
-                grid.push(GridCell::new(
-                    grid_size,
-                    Vec2::new(
-                        world_grid_origin.x + j as f32 * grid_size,
-                        world_grid_origin.y + i as f32 * grid_size,
-                    ),
-                ));
-            }
-        }
+        let mut grid = Self::build_grid(
+            world_grid_origin,
+            grid_size,
+            current_grid_cols,
+            current_grid_rows,
+        );
 
         for (index, particle) in particles.iter().enumerate() {
             let (grid_x, grid_y) = Self::get_grid_coords_internal(
@@ -194,17 +105,175 @@ impl Fluid {
             grid_size,
             particle_spawner,
             world_grid_origin,
+            rng: Rng::new(config.seed),
         }
     }
 
-    pub fn draw(&self) {
-        for particle in &self.particles {
-            particle.draw(750.);
+    /// Grid origin, columns and rows for the given fluid type. Liquids get an
+    /// extra ring of cells around the boundary to hold the ghost layer.
+    fn grid_layout(config: &Config, boundary: &Boundary) -> (Vec2, usize, usize) {
+        let grid_size = config.smoothing_radius;
+        let base_grid_cols = (boundary.width / grid_size).ceil().max(1.0) as usize;
+        let base_grid_rows = (boundary.height / grid_size).ceil().max(1.0) as usize;
+
+        match config.fluid_type {
+            FluidType::Liquid => (
+                boundary.pos - Vec2::new(grid_size, grid_size),
+                base_grid_cols + 2,
+                base_grid_rows + 2,
+            ),
+            FluidType::Gas => (boundary.pos, base_grid_cols, base_grid_rows),
         }
+    }
+
+    fn spawn_ghost_particles(
+        config: &Config,
+        world_grid_origin: Vec2,
+        extended_grid_cols: usize,
+        extended_grid_rows: usize,
+    ) -> Vec<Particle> {
+        let grid_size = config.smoothing_radius;
+        let ghost_layer_origin_x = world_grid_origin.x;
+        let ghost_layer_origin_y = world_grid_origin.y;
+
+        // Ghost particles
+        let mut ghost_particles_vec: Vec<Particle> = Vec::new();
+        let extended_grid_rows_f = extended_grid_rows as f32;
+
+        for r_idx in 0..extended_grid_rows {
+            for c_idx in 0..extended_grid_cols {
+                if !(r_idx == 0
+                    || r_idx == extended_grid_rows - 1
+                    || c_idx == 0
+                    || c_idx == extended_grid_cols - 1)
+                {
+                    continue;
+                }
 
-        for grid_box in &self.grid {
-            grid_box.draw();
+                let is_on_top_boundary = r_idx == 0;
+                if is_on_top_boundary {
+                    continue;
+                }
+
+                let is_on_left_boundary = c_idx == 0;
+                let is_on_right_boundary = c_idx == extended_grid_cols - 1;
+                let is_on_bottom_boundary = r_idx == extended_grid_rows - 1;
+
+                if is_on_left_boundary || is_on_right_boundary {
+                    let norm_y_in_col = (r_idx as f32) / (extended_grid_rows_f - 1.0);
+                    if norm_y_in_col < config.ghost_wall_start_percent {
+                        continue;
+                    }
+                }
+
+                let spacing_factor = {
+                    if is_on_bottom_boundary {
+                        1.0
+                    } else if is_on_left_boundary || is_on_right_boundary {
+                        let norm_y_in_col = (r_idx as f32) / (extended_grid_rows_f - 1.0);
+                        let range_norm_y = (norm_y_in_col - config.ghost_wall_start_percent)
+                            / (1.0 - config.ghost_wall_start_percent);
+                        let interp_t = range_norm_y.clamp(0.0, 1.0);
+                        config.start_ghost_spacing_multiplier * (1.0 - interp_t) + 1.0 * interp_t
+                    } else {
+                        1.0
+                    }
+                };
+
+                let current_spacing =
+                    config.particle_radius * config.target_ghost_spacing * spacing_factor;
+
+                if current_spacing <= 1e-3 {
+                    continue;
+                }
+
+                let count_x = (grid_size / current_spacing).floor().max(1.0) as u32;
+                let count_y = (grid_size / current_spacing).floor().max(1.0) as u32;
+
+                let margin_x = (grid_size - (count_x as f32 * current_spacing)) / 2.0;
+                let margin_y = (grid_size - (count_y as f32 * current_spacing)) / 2.0;
+
+                let cell_origin_x = ghost_layer_origin_x + c_idx as f32 * grid_size;
+                let cell_origin_y = ghost_layer_origin_y + r_idx as f32 * grid_size;
+
+                for i in 0..count_y {
+                    for j in 0..count_x {
+                        let px = cell_origin_x
+                            + margin_x
+                            + (j as f32 * current_spacing)
+                            + current_spacing * 0.5;
+                        let py = cell_origin_y
+                            + margin_y
+                            + (i as f32 * current_spacing)
+                            + current_spacing * 0.5;
+                        ghost_particles_vec.push(Particle::new(
+                            Vec2::new(px, py),
+                            config.particle_radius,
+                            true,
+                        ));
+                    }
+                }
+            }
+        }
+
+        ghost_particles_vec
+    }
+
+    /// Switches the fluid to `config.fluid_type` in place. Fluid particles keep
+    /// their state while the ghost layer and extended grid are added or
+    /// removed to match the new type.
+    pub fn apply_fluid_type(&mut self, config: &Config, boundary: &Boundary) {
+        self.particles.retain(|particle| !particle.is_ghost);
+
+        let (world_grid_origin, grid_cols, grid_rows) = Self::grid_layout(config, boundary);
+        if config.fluid_type == FluidType::Liquid {
+            self.particles.extend(Self::spawn_ghost_particles(
+                config,
+                world_grid_origin,
+                grid_cols,
+                grid_rows,
+            ));
         }
+
+        self.world_grid_origin = world_grid_origin;
+        self.grid_cols = grid_cols;
+        self.grid_rows = grid_rows;
+        self.grid_size = config.smoothing_radius;
+        self.rebuild_grid();
+    }
+
+    fn build_grid(
+        world_grid_origin: Vec2,
+        grid_size: f32,
+        grid_cols: usize,
+        grid_rows: usize,
+    ) -> Vec<GridCell> {
+        let mut grid: Vec<GridCell> = Vec::with_capacity(grid_cols * grid_rows);
+        for i in 0..grid_rows {
+            for j in 0..grid_cols {
+                grid.push(GridCell::new(
+                    grid_size,
+                    Vec2::new(
+                        world_grid_origin.x + j as f32 * grid_size,
+                        world_grid_origin.y + i as f32 * grid_size,
+                    ),
+                ));
+            }
+        }
+        grid
+    }
+
+    /// Recreates the grid cells from the current layout, e.g. after
+    /// deserialization, which skips them since they are derived from the
+    /// particles.
+    pub(crate) fn rebuild_grid(&mut self) {
+        self.grid = Self::build_grid(
+            self.world_grid_origin,
+            self.grid_size,
+            self.grid_cols,
+            self.grid_rows,
+        );
+        self.update_spatial_grid();
     }
 
     fn get_grid_coords_internal(
@@ -228,7 +297,13 @@ impl Fluid {
         grid_y * grid_cols + grid_x
     }
 
-    fn get_grid_coords(&self, position: Vec2) -> (usize, usize) {
+    /// Number of grid columns and rows.
+    pub fn grid_dimensions(&self) -> (usize, usize) {
+        (self.grid_cols, self.grid_rows)
+    }
+
+    /// The grid cell containing `position`, clamped to the grid.
+    pub fn get_grid_coords(&self, position: Vec2) -> (usize, usize) {
         Self::get_grid_coords_internal(
             position,
             self.world_grid_origin,
@@ -238,11 +313,14 @@ impl Fluid {
         )
     }
 
-    fn get_grid_index(&self, grid_x: usize, grid_y: usize) -> usize {
+    pub fn get_grid_index(&self, grid_x: usize, grid_y: usize) -> usize {
         Self::get_grid_index_internal(grid_x, grid_y, self.grid_cols)
     }
 
-    fn get_neighbor_particle_indices(&self, grid_x: usize, grid_y: usize) -> Vec<usize> {
+    /// Indices of the particles in the 3x3 block of cells around
+    /// `(grid_x, grid_y)`, as of the last `update_spatial_grid`. These are
+    /// candidates only; callers still check the distance.
+    pub fn get_neighbor_particle_indices(&self, grid_x: usize, grid_y: usize) -> Vec<usize> {
         let mut neighbors = Vec::new();
         for ny_offset in -1..=1 {
             for nx_offset in -1..=1 {
@@ -267,58 +345,102 @@ impl Fluid {
         neighbors
     }
 
-    pub fn update(&mut self, delta_time: f32, gravity: Vec2, config: &Config) {
+    /// Advances the fluid by up to `delta_time` and returns the time step
+    /// actually taken. The time spent in each phase is added to `timings`.
+    ///
+    /// With `adaptive_time_step` enabled the step is limited by the CFL and
+    /// force criteria and may be shorter than `delta_time`, in which case the
+    /// caller is expected to call `update` again for the remainder.
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        gravity: Vec2,
+        config: &Config,
+        timings: &mut PhaseTimings,
+    ) -> f32 {
+        // Particles are spawned first so that they take part in this step.
+        // The step actually taken is only known once the forces are computed,
+        // so the spawner advances by `delta_time` and gets back whatever part
+        // of it the adaptive step did not take.
         if let Some(spawner) = &mut self.particle_spawner {
-            spawner.update_flow_spawn(&mut self.particles, config, delta_time);
+            timings.time(Phase::Spawn, || {
+                spawner.update_flow_spawn(&mut self.particles, config, delta_time, &mut self.rng)
+            });
         }
+        let step_seed = self.rng.next_u64();
 
-        self.particles
-            .par_iter_mut()
-            .for_each(|particle| particle.predict_position());
+        timings.time(Phase::Predict, || {
+            self.particles
+                .par_iter_mut()
+                .for_each(|particle| particle.predict_position())
+        });
 
-        self.update_spatial_grid();
-        self.update_density(config);
+        timings.time(Phase::SpatialGrid, || self.update_spatial_grid());
+        timings.time(Phase::Density, || self.update_density(config));
 
-        let forces: Vec<Vec2> = self
-            .particles
-            .par_iter()
-            .enumerate()
-            .map(|(index, particle)| {
-                let (grid_x, grid_y) = self.get_grid_coords(particle.predicted_position);
-                let neighbor_particle_indices = self.get_neighbor_particle_indices(grid_x, grid_y);
+        let forces = timings.time(Phase::Forces, || self.calculate_forces(config, step_seed));
 
-                let pressure_force = Physics::calculate_pressure_force_on_particle(
-                    index,
-                    &neighbor_particle_indices,
-                    &self.particles,
-                    config.mass,
-                    config.smoothing_radius,
-                    config,
-                );
+        let requested_time = delta_time;
+        let delta_time = timings.time(Phase::Integration, || {
+            self.particles
+                .par_iter_mut()
+                .zip(forces.par_iter())
+                .for_each(|(particle, &force)| {
+                    if particle.density > 0.0 {
+                        particle.acceleration += force / particle.density;
+                    }
+                });
 
-                let viscosity_force = Physics::calculate_viscosity_from_neighbors(
-                    index,
-                    &neighbor_particle_indices,
-                    &self.particles,
-                    config.mass,
-                    config.smoothing_radius,
-                    config.viscosity_strength,
-                );
+            let delta_time = if config.adaptive_time_step {
+                self.adaptive_time_step(gravity, config).min(delta_time)
+            } else {
+                delta_time
+            };
 
-                pressure_force + viscosity_force
+            self.particles
+                .par_iter_mut()
+                .for_each(|particle| particle.update(delta_time, gravity));
+            delta_time
+        });
+
+        if let Some(spawner) = &mut self.particle_spawner {
+            spawner.return_unused_time(requested_time - delta_time);
+        }
+
+        delta_time
+    }
+
+    /// Picks a time step from the fastest and most accelerated particles so
+    /// that no particle travels more than a fraction of `smoothing_radius` in
+    /// one step, clamped to `[min_time_step, max_time_step]`.
+    fn adaptive_time_step(&self, gravity: Vec2, config: &Config) -> f32 {
+        let gravity_acceleration = gravity * DISTANCE_ZOOM;
+        let (max_speed, max_acceleration) = self
+            .particles
+            .par_iter()
+            .filter(|particle| !particle.is_ghost)
+            .map(|particle| {
+                (
+                    particle.velocity.length(),
+                    (particle.acceleration + gravity_acceleration).length(),
+                )
             })
-            .collect();
+            .reduce(
+                || (0.0, 0.0),
+                |a: (f32, f32), b: (f32, f32)| (a.0.max(b.0), a.1.max(b.1)),
+            );
 
-        self.particles
-            .par_iter_mut()
-            .zip(forces.par_iter())
-            .for_each(|(particle, &force)| {
-                if particle.density > 0.0 {
-                    particle.acceleration += force / particle.density;
-                }
+        let smoothing_radius = config.smoothing_radius;
+        let mut delta_time = config.max_time_step;
+        if max_speed > 0.0 {
+            delta_time = delta_time.min(config.cfl_factor * smoothing_radius / max_speed);
+        }
+        if max_acceleration > 0.0 {
+            delta_time =
+                delta_time.min(config.force_factor * (smoothing_radius / max_acceleration).sqrt());
+        }
 
-                particle.update(delta_time, gravity);
-            });
+        delta_time.clamp(config.min_time_step, config.max_time_step)
     }
 
     pub fn update_spatial_grid(&mut self) {
@@ -377,6 +499,89 @@ impl Fluid {
             });
     }
 
+    /// Pressure and viscosity force on every particle, from the current grid
+    /// and densities. `step_seed` seeds the random direction used to separate
+    /// particles at the same position.
+    pub fn calculate_forces(&self, config: &Config, step_seed: u64) -> Vec<Vec2> {
+        self.particles
+            .par_iter()
+            .enumerate()
+            .map(|(index, particle)| {
+                let (grid_x, grid_y) = self.get_grid_coords(particle.predicted_position);
+                let neighbor_particle_indices = self.get_neighbor_particle_indices(grid_x, grid_y);
+
+                let pressure_force = Physics::calculate_pressure_force_on_particle(
+                    index,
+                    &neighbor_particle_indices,
+                    &self.particles,
+                    config.mass,
+                    config.smoothing_radius,
+                    config,
+                    step_seed,
+                );
+
+                let viscosity_force = Physics::calculate_viscosity_from_neighbors(
+                    index,
+                    &neighbor_particle_indices,
+                    &self.particles,
+                    config.mass,
+                    config.smoothing_radius,
+                    config.viscosity_strength,
+                );
+
+                pressure_force + viscosity_force
+            })
+            .collect()
+    }
+
+    /// Vorticity of every particle from the current grid, see
+    /// `Physics::calculate_vorticity_from_neighbors`.
+    pub fn vorticity(&self, config: &Config) -> Vec<f32> {
+        self.particles
+            .par_iter()
+            .enumerate()
+            .map(|(index, particle)| {
+                let (grid_x, grid_y) = self.get_grid_coords(particle.predicted_position);
+                let neighbor_particle_indices = self.get_neighbor_particle_indices(grid_x, grid_y);
+                Physics::calculate_vorticity_from_neighbors(
+                    index,
+                    &neighbor_particle_indices,
+                    &self.particles,
+                    config.mass,
+                    config.smoothing_radius,
+                )
+            })
+            .collect()
+    }
+
+    /// The velocity field at an arbitrary point, see
+    /// `Physics::interpolate_velocity_from_neighbors`.
+    pub fn velocity_at(&self, point: Vec2, config: &Config) -> Option<Vec2> {
+        let (grid_x, grid_y) = self.get_grid_coords(point);
+        let neighbor_particle_indices = self.get_neighbor_particle_indices(grid_x, grid_y);
+        Physics::interpolate_velocity_from_neighbors(
+            point,
+            &neighbor_particle_indices,
+            &self.particles,
+            config.mass,
+            config.smoothing_radius,
+        )
+    }
+
+    /// The neighbours the solver uses for `index`: the particles in the 3x3
+    /// candidate cells whose predicted positions are within `radius`.
+    pub fn neighbors_in_radius(&self, index: usize, radius: f32) -> Vec<usize> {
+        let position = self.particles[index].predicted_position;
+        let (grid_x, grid_y) = self.get_grid_coords(position);
+        self.get_neighbor_particle_indices(grid_x, grid_y)
+            .into_iter()
+            .filter(|&other| {
+                other != index
+                    && position.distance(self.particles[other].predicted_position) <= radius
+            })
+            .collect()
+    }
+
     pub fn handle_interaction(
         &mut self,
         click_point: Vec2,
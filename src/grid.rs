@@ -1,5 +1,6 @@
-use macroquad::prelude::*;
+use glam::Vec2;
 
+#[derive(Clone)]
 pub struct GridCell {
     pub particles: Vec<usize>,
     pub grid_size: f32,
@@ -22,19 +23,4 @@ impl GridCell {
     pub fn clear(&mut self) {
         self.particles.clear();
     }
-
-    pub fn draw(&self) {
-        let grid_color = Color::new(0.5, 0.5, 0.5, 0.25);
-        let grid_width = self.grid_size;
-        let grid_height = self.grid_size;
-
-        draw_rectangle_lines(
-            self.position.x,
-            self.position.y,
-            grid_width,
-            grid_height,
-            1.0,
-            grid_color,
-        );
-    }
 }
@@ -0,0 +1,252 @@
+use clap::Args;
+use fluid_simulation::export::{self, ExportFormat};
+use fluid_simulation::particle::Particle;
+use fluid_simulation::profiler::{Phase, PhaseTimings, TraceWriter};
+use fluid_simulation::raster::{self, FrameOptions, RenderMode};
+use fluid_simulation::replay::Player;
+use fluid_simulation::velocity_field::VelocityFieldMode;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use crate::InitialState;
+
+/// Largest accepted image width or height, in pixels.
+const MAX_IMAGE_SIZE: i64 = 16384;
+
+#[derive(Args, Debug)]
+pub struct HeadlessArgs {
+    /// Number of simulation steps to run
+    #[clap(long, default_value_t = 1000, requires = "headless")]
+    pub steps: u32,
+
+    /// Fixed time step in seconds, overrides `time_step` from the config
+    #[clap(long, requires = "headless")]
+    pub delta_time: Option<f32>,
+
+    /// Write a snapshot every N steps and after the last step (0 disables
+    /// snapshots)
+    #[clap(long, default_value_t = 100, requires = "headless")]
+    pub snapshot_interval: u32,
+
+    /// Formats written for each snapshot, comma separated
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "csv",
+        requires = "headless"
+    )]
+    pub format: Vec<ExportFormat>,
+
+    /// Write a checkpoint every N steps (0 disables checkpoints)
+    #[clap(long, default_value_t = 0, requires = "headless")]
+    pub checkpoint_interval: u32,
+
+    #[clap(long, default_value = "output", requires = "headless")]
+    pub output_dir: PathBuf,
+
+    /// Render a PNG image every N steps and after the last step (0 disables
+    /// images)
+    #[clap(long, default_value_t = 0, requires = "headless")]
+    pub image_interval: u32,
+
+    #[clap(
+        long,
+        default_value_t = 1280,
+        value_parser = clap::value_parser!(u32).range(1..=MAX_IMAGE_SIZE),
+        requires = "headless"
+    )]
+    pub image_width: u32,
+
+    #[clap(
+        long,
+        default_value_t = 720,
+        value_parser = clap::value_parser!(u32).range(1..=MAX_IMAGE_SIZE),
+        requires = "headless"
+    )]
+    pub image_height: u32,
+
+    /// How the fluid is drawn in images
+    #[clap(long, value_enum, default_value = "particles", requires = "headless")]
+    pub image_mode: RenderMode,
+
+    /// Draw the spatial grid in images
+    #[clap(long, requires = "headless")]
+    pub image_grid: bool,
+
+    /// Draw ghost particles in images
+    #[clap(long, requires = "headless")]
+    pub image_ghosts: bool,
+
+    /// Draw the velocity field in images
+    #[clap(long, value_enum, default_value = "off", requires = "headless")]
+    pub image_velocity_field: VelocityFieldMode,
+
+    /// Draw the particle count of each grid cell in images
+    #[clap(long, requires = "headless")]
+    pub image_occupancy: bool,
+
+    /// Highlight the neighbours of the particle with this index in images
+    #[clap(long, value_name = "INDEX", requires = "headless")]
+    pub image_neighbors: Option<usize>,
+
+    /// Draw the colour map legend in images
+    #[clap(long, requires = "headless")]
+    pub image_legend: bool,
+}
+
+/// Runs the simulation for `args.steps` steps, applying the recorded input
+/// of `player` if given and writing the phase timings of every step to
+/// `trace` if given.
+pub fn run(
+    initial_state: InitialState,
+    mut player: Option<Player>,
+    mut trace: Option<TraceWriter>,
+    args: &HeadlessArgs,
+) -> ExitCode {
+    if let Err(e) = fs::create_dir_all(&args.output_dir) {
+        eprintln!(
+            "Failed to create output directory '{}': {}",
+            args.output_dir.display(),
+            e
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let mut simulation = initial_state.into_simulation();
+    if let Some(delta_time) = args.delta_time {
+        simulation.config_mut().time_step = delta_time;
+        if let Err(e) = simulation.config().validate() {
+            eprintln!("Error: --delta-time {}: {}", delta_time, e);
+            return ExitCode::FAILURE;
+        }
+    }
+    let frame_options = FrameOptions {
+        width: args.image_width,
+        height: args.image_height,
+        render_mode: args.image_mode,
+        show_grid: args.image_grid,
+        show_ghosts: args.image_ghosts,
+        velocity_field: args.image_velocity_field,
+        show_occupancy: args.image_occupancy,
+        highlighted_particle: args.image_neighbors,
+        show_legend: args.image_legend,
+    };
+    let mut total_timings = PhaseTimings::default();
+
+    for run_step in 1..=args.steps {
+        if let Some(player) = &mut player {
+            player.apply_due(&mut simulation);
+        }
+        simulation.step();
+        let mut timings = simulation.take_timings();
+        // Numbered by the simulation's own step count so that a resumed run
+        // continues the numbering of the run it was checkpointed from.
+        let step = simulation.step_count();
+
+        if !is_finite(&simulation.fluid().particles) {
+            eprintln!("Simulation became unstable at step {}", step);
+            return ExitCode::FAILURE;
+        }
+
+        let is_snapshot_step = args.snapshot_interval > 0
+            && (step.is_multiple_of(args.snapshot_interval as u64) || run_step == args.steps);
+        if is_snapshot_step {
+            for &format in &args.format {
+                let path = args
+                    .output_dir
+                    .join(format!("step_{:06}.{}", step, format.extension()));
+                if let Err(e) = export::write_frame(
+                    &path,
+                    format,
+                    &simulation.fluid().particles,
+                    simulation.config(),
+                ) {
+                    eprintln!("Failed to write snapshot '{}': {}", path.display(), e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+
+        let is_image_step = args.image_interval > 0
+            && (step.is_multiple_of(args.image_interval as u64) || run_step == args.steps);
+        if is_image_step {
+            let path = args.output_dir.join(format!("frame_{:06}.png", step));
+            let canvas = timings.time(Phase::Render, || {
+                raster::render_frame(
+                    simulation.fluid(),
+                    simulation.boundary(),
+                    simulation.config(),
+                    &frame_options,
+                )
+            });
+            if let Err(e) = canvas.write_png(&path) {
+                eprintln!("Failed to write image '{}': {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        }
+
+        if args.checkpoint_interval > 0 && step.is_multiple_of(args.checkpoint_interval as u64) {
+            let path = args.output_dir.join(format!("checkpoint_{:06}.json", step));
+            if let Err(e) = simulation.to_checkpoint().save(&path) {
+                eprintln!("Failed to write checkpoint '{}': {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        }
+
+        if let Some(trace) = &mut trace
+            && let Err(e) = trace.write_row(
+                run_step as u64,
+                step,
+                simulation.fluid().particles.len(),
+                &timings,
+            )
+        {
+            eprintln!("Failed to write profile trace: {}", e);
+            return ExitCode::FAILURE;
+        }
+        total_timings.add_all(&timings);
+    }
+
+    if let Some(trace) = &mut trace
+        && let Err(e) = trace.flush()
+    {
+        eprintln!("Failed to write profile trace: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    println!(
+        "Ran {} steps, output written to '{}'",
+        args.steps,
+        args.output_dir.display()
+    );
+    print_timings(&total_timings, args.steps);
+    ExitCode::SUCCESS
+}
+
+/// Prints the mean time per step spent in each phase and its share of the
+/// total.
+fn print_timings(timings: &PhaseTimings, steps: u32) {
+    let total = timings.total().as_secs_f64();
+    if steps == 0 || total == 0.0 {
+        return;
+    }
+    println!("Mean time per step:");
+    for phase in Phase::ALL {
+        let time = timings.get(phase).as_secs_f64();
+        println!(
+            "  {:<13} {:9.3} ms {:5.1}%",
+            phase.label(),
+            time * 1000.0 / steps as f64,
+            time / total * 100.0
+        );
+    }
+    println!("  {:<13} {:9.3} ms", "total", total * 1000.0 / steps as f64);
+}
+
+fn is_finite(particles: &[Particle]) -> bool {
+    particles
+        .iter()
+        .all(|p| p.position.is_finite() && p.velocity.is_finite())
+}
@@ -0,0 +1,18 @@
+pub mod boundary;
+pub mod checkpoint;
+pub mod colormap;
+pub mod config;
+pub mod export;
+pub mod fluid;
+mod font;
+pub mod grid;
+pub mod particle;
+pub mod physics;
+pub mod profiler;
+pub mod raster;
+pub mod replay;
+pub mod rng;
+pub mod simulation;
+pub mod spawner;
+pub mod surface;
+pub mod velocity_field;
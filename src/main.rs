@@ -1,50 +1,189 @@
 use macroquad::prelude::*;
 
-mod boundary;
-mod config;
-mod fluid;
-mod grid;
-mod particle;
-mod physics;
-mod simulation;
-mod spawner;
-
-use crate::config::{Config, FluidSpawnMode, FluidType};
-use crate::simulation::Simulation;
+mod app;
+mod config_watcher;
+mod headless;
+mod overlay;
+mod panel;
+mod profile_hud;
+mod render;
+mod view;
+
+use crate::app::App;
+use crate::config_watcher::ConfigWatcher;
+use crate::headless::HeadlessArgs;
 use clap::Parser;
-use std::fs;
+use fluid_simulation::checkpoint::Checkpoint;
+use fluid_simulation::config::{Config, ConfigError, ConfigOverride, FluidSpawnMode, FluidType};
+use fluid_simulation::profiler::{TraceFormat, TraceWriter};
+use fluid_simulation::raster;
+use fluid_simulation::replay::{Player, Replay};
+use fluid_simulation::simulation::Simulation;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Instant;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
-    #[clap(long, short = 'f', value_enum)]
+    #[clap(long, short = 'f', value_enum, conflicts_with_all = ["resume", "replay"])]
     fluid_type: Option<FluidType>,
 
-    #[clap(long, short = 's', value_enum)]
+    #[clap(long, short = 's', value_enum, conflicts_with_all = ["resume", "replay"])]
     spawn_mode: Option<FluidSpawnMode>,
 
+    /// Seed for the simulation's random number generator
+    #[clap(long, conflicts_with_all = ["resume", "replay"])]
+    seed: Option<u64>,
+
     #[clap(long, default_value = "config.toml")]
-    config_file: String,
+    config_file: PathBuf,
+
+    /// Override a config value, e.g. `--set liquid.viscosity_strength=5` (repeatable)
+    #[clap(
+        long = "set",
+        value_name = "KEY=VALUE",
+        conflicts_with_all = ["resume", "replay"]
+    )]
+    overrides: Vec<ConfigOverride>,
+
+    /// Print the full default configuration as TOML and exit
+    #[clap(long)]
+    print_default_config: bool,
+
+    /// Resume from a checkpoint file instead of starting from the config
+    #[clap(long, conflicts_with = "replay")]
+    resume: Option<PathBuf>,
+
+    /// Play back a recorded input session from its starting state
+    #[clap(long)]
+    replay: Option<PathBuf>,
+
+    /// Write per-phase timings to a trace file, one row per frame (or per
+    /// step when headless)
+    #[clap(long, value_name = "FILE")]
+    profile_trace: Option<PathBuf>,
+
+    /// Format of the profile trace
+    #[clap(long, value_enum, default_value = "csv", requires = "profile_trace")]
+    trace_format: TraceFormat,
+
+    /// Run without a window and write snapshots to the output directory
+    #[clap(long)]
+    headless: bool,
+
+    #[clap(flatten)]
+    headless_args: HeadlessArgs,
 }
 
-fn window_conf() -> Conf {
+fn window_conf(config: &Config) -> Conf {
     Conf {
         window_title: "Fluid Simulation".to_owned(),
-        fullscreen: true,
-        window_resizable: false,
+        fullscreen: config.fullscreen,
+        window_width: config.window_width as i32,
+        window_height: config.window_height as i32,
+        window_resizable: true,
         ..Default::default()
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
+/// Where the simulation starts from: a fresh config or a saved checkpoint.
+pub enum InitialState {
+    Config(Config),
+    Checkpoint(Checkpoint),
+}
+
+impl InitialState {
+    pub fn config(&self) -> &Config {
+        match self {
+            InitialState::Config(config) => config,
+            InitialState::Checkpoint(checkpoint) => &checkpoint.config,
+        }
+    }
+
+    pub fn into_simulation(self) -> Simulation {
+        match self {
+            InitialState::Config(config) => Simulation::new(config),
+            InitialState::Checkpoint(checkpoint) => Simulation::from_checkpoint(checkpoint),
+        }
+    }
+}
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    let config_str = fs::read_to_string(&cli.config_file)
-        .unwrap_or_else(|e| panic!("Failed to read config file '{}': {}", cli.config_file, e));
+    if cli.print_default_config {
+        print!("{}", Config::default_toml());
+        return ExitCode::SUCCESS;
+    }
+
+    let mut player = None;
+    let initial_state = match (&cli.resume, &cli.replay) {
+        (_, Some(path)) => match Replay::load(path) {
+            Ok(replay) => {
+                let (replay_player, checkpoint) = Player::new(replay);
+                if let Err(e) = checkpoint.config.validate() {
+                    eprintln!("Error: replay '{}' has an {}", path.display(), e);
+                    return ExitCode::FAILURE;
+                }
+                player = Some(replay_player);
+                InitialState::Checkpoint(checkpoint)
+            }
+            Err(e) => {
+                eprintln!("Error: failed to load replay '{}': {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        (Some(path), None) => match Checkpoint::load(path) {
+            Ok(checkpoint) => InitialState::Checkpoint(checkpoint),
+            Err(e) => {
+                eprintln!(
+                    "Error: failed to load checkpoint '{}': {}",
+                    path.display(),
+                    e
+                );
+                return ExitCode::FAILURE;
+            }
+        },
+        (None, None) => match load_config(&cli) {
+            Ok(config) => InitialState::Config(config),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    let trace = match &cli.profile_trace {
+        Some(path) => match TraceWriter::create(path, cli.trace_format) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                eprintln!(
+                    "Error: failed to create profile trace '{}': {}",
+                    path.display(),
+                    e
+                );
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    if cli.headless {
+        return headless::run(initial_state, player, trace, &cli.headless_args);
+    }
+
+    let config_watcher = ConfigWatcher::new(cli.config_file.clone(), cli.overrides.clone());
+    let window_conf = window_conf(initial_state.config());
+    macroquad::Window::from_config(
+        window_conf,
+        run(initial_state, config_watcher, player, trace),
+    );
+    ExitCode::SUCCESS
+}
 
-    let mut config: Config = toml::from_str(&config_str)
-        .unwrap_or_else(|e| panic!("Failed to parse config file '{}': {}", cli.config_file, e));
+fn load_config(cli: &Cli) -> Result<Config, ConfigError> {
+    let mut config = Config::from_file(&cli.config_file, &cli.overrides)?;
 
     if let Some(ft) = cli.fluid_type {
         config.fluid_type = ft;
@@ -52,14 +191,23 @@ async fn main() {
     if let Some(sm) = cli.spawn_mode {
         config.fluid_spawn_mode = sm;
     }
-
-    if config.fluid_type == FluidType::Gas && config.fluid_spawn_mode == FluidSpawnMode::Flow {
-        panic!("Flow spawn mode is not supported for Gas fluid type.");
+    if let Some(seed) = cli.seed {
+        config.seed = seed;
     }
 
+    config.validate()?;
     config.adapt_to_fluid_type();
+    Ok(config)
+}
 
-    let mut simulation = Simulation::new(config);
+async fn run(
+    initial_state: InitialState,
+    config_watcher: ConfigWatcher,
+    player: Option<Player>,
+    trace: Option<TraceWriter>,
+) {
+    let simulation = initial_state.into_simulation();
+    let mut app = App::new(simulation, config_watcher, player, trace);
 
     let mut fps_update_timer = 0.0;
     let mut avg_fps = 0;
@@ -68,10 +216,12 @@ async fn main() {
 
     loop {
         let frame_time = get_frame_time();
-        clear_background(BLACK);
-        simulation.handle_input();
-        simulation.update(frame_time);
-        simulation.render();
+        clear_background(Color::from(raster::BACKGROUND_COLOR));
+        app.handle_input();
+        app.update(frame_time);
+        let render_start = Instant::now();
+        app.render();
+        app.end_frame(frame_time, render_start.elapsed());
 
         total_time += frame_time;
         frame_count += 1;
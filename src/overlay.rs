@@ -0,0 +1,193 @@
+use fluid_simulation::{
+    boundary::Boundary,
+    config::Config,
+    fluid::Fluid,
+    raster::{self, MIN_COUNT_CELL_SIZE},
+    velocity_field::{VelocityField, VelocityFieldMode},
+};
+use macroquad::prelude::*;
+
+use crate::render;
+
+/// Debug views of the solver's internals, toggled with F1 to F4, and the
+/// velocity field, cycled with V.
+pub struct DebugOverlays {
+    pub show_grid: bool,
+    pub show_ghosts: bool,
+    pub show_neighbors: bool,
+    pub show_occupancy: bool,
+    pub velocity_field: VelocityFieldMode,
+}
+
+impl DebugOverlays {
+    pub fn new() -> Self {
+        Self {
+            show_grid: true,
+            show_ghosts: false,
+            show_neighbors: false,
+            show_occupancy: false,
+            velocity_field: VelocityFieldMode::Off,
+        }
+    }
+
+    pub fn handle_input(&mut self) {
+        if is_key_pressed(KeyCode::F1) {
+            self.show_grid = !self.show_grid;
+        }
+        if is_key_pressed(KeyCode::F2) {
+            self.show_ghosts = !self.show_ghosts;
+        }
+        if is_key_pressed(KeyCode::F3) {
+            self.show_neighbors = !self.show_neighbors;
+        }
+        if is_key_pressed(KeyCode::F4) {
+            self.show_occupancy = !self.show_occupancy;
+        }
+        if is_key_pressed(KeyCode::V) {
+            self.velocity_field = self.velocity_field.next();
+        }
+    }
+
+    /// Draws the overlays that live in world space, call with the simulation
+    /// camera set.
+    pub fn draw_world(
+        &self,
+        fluid: &Fluid,
+        boundary: &Boundary,
+        config: &Config,
+        camera: &Camera2D,
+    ) {
+        if self.velocity_field != VelocityFieldMode::Off {
+            let field = VelocityField::compute(self.velocity_field, fluid, boundary, config);
+            render::draw_velocity_field(&field, self.velocity_field);
+        }
+        if self.show_neighbors
+            && let Some(hovered) = self.hovered_particle(fluid, camera)
+        {
+            draw_neighbors(fluid, config, hovered);
+        }
+    }
+
+    /// Draws the overlays that live in screen space, call with the default
+    /// camera set.
+    pub fn draw_screen(&self, fluid: &Fluid, config: &Config, camera: &Camera2D) {
+        if self.show_occupancy {
+            draw_occupancy(fluid, camera);
+        }
+        if self.show_neighbors
+            && let Some(hovered) = self.hovered_particle(fluid, camera)
+        {
+            let particle = &fluid.particles[hovered];
+            let neighbor_count = fluid
+                .neighbors_in_radius(hovered, config.smoothing_radius)
+                .len();
+            let text = format!(
+                "Particle {}{}: {} neighbours, density {:.1}, near density {:.1}",
+                hovered,
+                if particle.is_ghost { " (ghost)" } else { "" },
+                neighbor_count,
+                particle.density,
+                particle.near_density
+            );
+            draw_text(
+                &text,
+                10.0,
+                screen_height() - 34.0,
+                20.0,
+                Color::from(raster::NEIGHBOR_COLOR),
+            );
+        }
+    }
+
+    /// The visible particle under the mouse, preferring the closest one.
+    fn hovered_particle(&self, fluid: &Fluid, camera: &Camera2D) -> Option<usize> {
+        let point = camera.screen_to_world(mouse_position().into());
+        fluid
+            .particles
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| self.show_ghosts || !p.is_ghost)
+            .map(|(index, p)| (index, p.position.distance(point), p.radius))
+            .filter(|&(_, distance, radius)| distance <= radius * 2.0)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _, _)| index)
+    }
+}
+
+fn draw_neighbors(fluid: &Fluid, config: &Config, index: usize) {
+    let particle = &fluid.particles[index];
+    let (grid_x, grid_y) = fluid.get_grid_coords(particle.predicted_position);
+    let (grid_cols, grid_rows) = fluid.grid_dimensions();
+
+    for ny in grid_y.saturating_sub(1)..=(grid_y + 1).min(grid_rows - 1) {
+        for nx in grid_x.saturating_sub(1)..=(grid_x + 1).min(grid_cols - 1) {
+            if let Some(cell) = fluid.grid.get(fluid.get_grid_index(nx, ny)) {
+                draw_rectangle(
+                    cell.position.x,
+                    cell.position.y,
+                    cell.grid_size,
+                    cell.grid_size,
+                    Color::from(raster::NEIGHBOR_CELL_COLOR),
+                );
+            }
+        }
+    }
+
+    let neighbor_color = Color::from(raster::NEIGHBOR_COLOR);
+    for other in fluid.neighbors_in_radius(index, config.smoothing_radius) {
+        let other = &fluid.particles[other];
+        draw_circle_lines(
+            other.position.x,
+            other.position.y,
+            other.radius + 1.0,
+            1.0,
+            neighbor_color,
+        );
+    }
+
+    draw_circle_lines(
+        particle.predicted_position.x,
+        particle.predicted_position.y,
+        config.smoothing_radius,
+        1.0,
+        neighbor_color,
+    );
+    draw_circle(
+        particle.position.x,
+        particle.position.y,
+        particle.radius + 1.0,
+        WHITE,
+    );
+}
+
+fn draw_occupancy(fluid: &Fluid, camera: &Camera2D) {
+    for cell in &fluid.grid {
+        if cell.particles.is_empty() {
+            continue;
+        }
+        let top_left = camera.world_to_screen(cell.position);
+        let bottom_right = camera.world_to_screen(cell.position + Vec2::splat(cell.grid_size));
+        let size = bottom_right - top_left;
+        if size.x < MIN_COUNT_CELL_SIZE {
+            continue;
+        }
+        let is_on_screen = bottom_right.x >= 0.0
+            && bottom_right.y >= 0.0
+            && top_left.x <= screen_width()
+            && top_left.y <= screen_height();
+        if !is_on_screen {
+            continue;
+        }
+
+        let text = cell.particles.len().to_string();
+        let font_size = (size.y * 0.4).min(32.0);
+        let dimensions = measure_text(&text, None, font_size as u16, 1.0);
+        draw_text(
+            &text,
+            top_left.x + (size.x - dimensions.width) / 2.0,
+            top_left.y + (size.y + dimensions.offset_y) / 2.0,
+            font_size,
+            WHITE,
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use fluid_simulation::config::{Config, FluidType, FluidTypeSpecifics};
+use macroquad::hash;
+use macroquad::prelude::*;
+use macroquad::ui::{Ui, root_ui, widgets};
+use std::ops::Range;
+
+const PANEL_POSITION: Vec2 = vec2(10.0, 40.0);
+const PANEL_SIZE: Vec2 = vec2(320.0, 300.0);
+
+/// A slider's label, its default range and the config value it edits.
+struct SliderSpec {
+    label: &'static str,
+    range: Range<f32>,
+    value: fn(&mut Config) -> &mut f32,
+}
+
+/// The sliders for the runtime-tunable parameters. The per-fluid-type
+/// parameters differ by orders of magnitude between liquid and gas, so their
+/// ranges do too. Ranges start above 0 for fields that `Config::validate`
+/// requires to be positive.
+fn slider_specs(fluid_type: FluidType) -> [SliderSpec; 9] {
+    let (target_density, pressure, near_pressure) = match fluid_type {
+        FluidType::Liquid => (1.0..10000.0, 0.0..2000.0, 0.0..500.0),
+        FluidType::Gas => (0.1..500.0, 0.0..500.0, 0.0..100.0),
+    };
+    [
+        SliderSpec {
+            label: "pressure",
+            range: pressure,
+            value: |config| &mut active_specifics(config).pressure_multiplier,
+        },
+        SliderSpec {
+            label: "near pressure",
+            range: near_pressure,
+            value: |config| &mut active_specifics(config).near_pressure_multiplier,
+        },
+        SliderSpec {
+            label: "viscosity",
+            range: 0.0..20.0,
+            value: |config| &mut active_specifics(config).viscosity_strength,
+        },
+        SliderSpec {
+            label: "target density",
+            range: target_density,
+            value: |config| &mut active_specifics(config).target_density,
+        },
+        SliderSpec {
+            label: "gravity x",
+            range: -2.0..2.0,
+            value: |config| &mut active_specifics(config).gravity.x,
+        },
+        SliderSpec {
+            label: "gravity y",
+            range: -2.0..2.0,
+            value: |config| &mut active_specifics(config).gravity.y,
+        },
+        SliderSpec {
+            label: "interaction strength",
+            range: 0.0..10000.0,
+            value: |config| &mut active_specifics(config).interaction_strength,
+        },
+        SliderSpec {
+            label: "interaction radius",
+            range: 0.0..500.0,
+            value: |config| &mut config.interaction_radius,
+        },
+        SliderSpec {
+            label: "boundary damping",
+            range: 0.0..1.0,
+            value: |config| &mut config.boundary_damping,
+        },
+    ]
+}
+
+/// The table the sliders edit: the one for the current fluid type.
+fn active_specifics(config: &mut Config) -> &mut FluidTypeSpecifics {
+    match config.fluid_type {
+        FluidType::Liquid => &mut config.liquid,
+        FluidType::Gas => &mut config.gas,
+    }
+}
+
+/// What the user did with the panel this frame.
+pub enum PanelAction {
+    /// A slider was moved or the parameters were reset, giving the edited
+    /// config.
+    Changed(Box<Config>),
+    SwitchFluidType,
+}
+
+/// On-screen sliders for the runtime-tunable parameters.
+pub struct ParameterPanel {
+    is_visible: bool,
+    baseline: Config,
+    /// The values the sliders showed last frame, to tell the user's edits
+    /// apart from changes made elsewhere.
+    last_shown: Option<Config>,
+}
+
+impl ParameterPanel {
+    pub fn new(baseline: Config) -> Self {
+        Self {
+            is_visible: false,
+            baseline,
+            last_shown: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_visible = !self.is_visible;
+    }
+
+    /// The config that the reset button restores.
+    pub fn set_baseline(&mut self, baseline: Config) {
+        self.baseline = baseline;
+    }
+
+    pub fn is_mouse_over(&self) -> bool {
+        self.is_visible && root_ui().is_mouse_over(mouse_position().into())
+    }
+
+    /// Shows the panel with sliders for the values in `config`. Returns the
+    /// edited config only when the user changed a value and the result is
+    /// valid, so that nothing is applied while the panel is hidden or idle.
+    pub fn draw(&mut self, config: &Config) -> Option<PanelAction> {
+        if !self.is_visible {
+            return None;
+        }
+
+        let mut edited = config.clone();
+        let mut switch_fluid_type = false;
+        let mut reset = false;
+        let mut baseline = self.baseline.clone();
+        baseline.fluid_type = config.fluid_type;
+        let mut last_shown = self.last_shown.take();
+
+        widgets::Window::new(hash!(), PANEL_POSITION, PANEL_SIZE)
+            .label("Parameters")
+            .ui(&mut root_ui(), |ui| {
+                Self::draw_sliders(ui, &mut edited, &mut baseline, last_shown.as_mut());
+
+                ui.separator();
+                let switch_label = match config.fluid_type {
+                    FluidType::Liquid => "Switch to gas",
+                    FluidType::Gas => "Switch to liquid",
+                };
+                switch_fluid_type = ui.button(None, switch_label);
+                reset = ui.button(None, "Reset");
+            });
+
+        self.last_shown = Some(edited.clone());
+        if switch_fluid_type {
+            return Some(PanelAction::SwitchFluidType);
+        }
+        if reset {
+            edited.apply_tunables(&self.baseline);
+        }
+        (edited != *config && edited.validate().is_ok())
+            .then(|| PanelAction::Changed(Box::new(edited)))
+    }
+
+    /// Draws a slider for each tunable value of `config`, with its range
+    /// widened to include the current and `baseline` values.
+    ///
+    /// A slider re-reads a value that changed elsewhere (a reload, a fluid
+    /// type switch or a reset) rounded to two decimals and clamped to its
+    /// range, so values that differ from `last_shown` are kept as they are
+    /// rather than taken from the slider.
+    fn draw_sliders(
+        ui: &mut Ui,
+        config: &mut Config,
+        baseline: &mut Config,
+        mut last_shown: Option<&mut Config>,
+    ) {
+        for spec in slider_specs(config.fluid_type) {
+            let value = *(spec.value)(config);
+            let baseline_value = *(spec.value)(baseline);
+            let range = spec.range.start.min(value).min(baseline_value)
+                ..spec.range.end.max(value).max(baseline_value);
+            let was_shown = last_shown
+                .as_deref_mut()
+                .is_some_and(|last_shown| *(spec.value)(last_shown) == value);
+
+            let edited_value = (spec.value)(config);
+            ui.slider(hash!(spec.label), spec.label, range, edited_value);
+            if !was_shown {
+                *edited_value = value;
+            }
+        }
+
+        // Sliders edit the table for the current fluid type, so re-apply it
+        // to the active parameters.
+        config.adapt_to_fluid_type();
+    }
+}
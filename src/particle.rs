@@ -1,7 +1,9 @@
-use macroquad::prelude::*;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
 
 use crate::simulation::DISTANCE_ZOOM;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Particle {
     pub position: Vec2,
     pub predicted_position: Vec2,
@@ -27,33 +29,6 @@ impl Particle {
         }
     }
 
-    pub fn draw(&self, max_speed: f32) {
-        if self.is_ghost {
-            return;
-        }
-
-        let speed = self.velocity.length();
-
-        let normalized_speed = (speed / max_speed).min(1.0);
-
-        // Color transitions: Blue (0,0,1) -> Cyan (0,1,1) -> Yellow (1,1,0) -> Red (1,0,0)
-        let color = if normalized_speed < 0.2 {
-            // Blue to Cyan (0.0 - 0.2)
-            let t = normalized_speed / 0.2;
-            Color::new(0.0, t, 1.0, 1.0)
-        } else if normalized_speed < 0.5 {
-            // Cyan to Yellow (0.2 - 0.5)
-            let t = (normalized_speed - 0.2) / 0.3;
-            Color::new(t, 1.0, 1.0 - t, 1.0)
-        } else {
-            // Yellow to Red (0.5 - 1.0)
-            let t = (normalized_speed - 0.5) / 0.5;
-            Color::new(1.0, 1.0 - t, 0.0, 1.0)
-        };
-
-        draw_circle(self.position.x, self.position.y, self.radius, color);
-    }
-
     pub fn predict_position(&mut self) {
         self.predicted_position = self.position + self.velocity * 1. / 30.;
     }
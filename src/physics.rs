@@ -1,7 +1,7 @@
-use macroquad::prelude::*;
+use glam::Vec2;
 use std::f32::consts::PI;
 
-use crate::{config::Config, particle::Particle, simulation::DISTANCE_ZOOM};
+use crate::{config::Config, particle::Particle, rng, simulation::DISTANCE_ZOOM};
 
 pub struct Physics;
 
@@ -146,6 +146,31 @@ impl Physics {
             })
     }
 
+    /// The SPH-interpolated velocity at `point`, normalised by the summed
+    /// kernel weights so that it is not damped near the free surface. Ghost
+    /// particles are skipped. Returns `None` if no fluid particle is within
+    /// `smoothing_radius`.
+    pub fn interpolate_velocity_from_neighbors(
+        point: Vec2,
+        neighbor_particle_indices: &[usize],
+        particles: &[Particle],
+        mass: f32,
+        smoothing_radius: f32,
+    ) -> Option<Vec2> {
+        let (velocity, total_weight) = neighbor_particle_indices
+            .iter()
+            .map(|&neighbor_index| &particles[neighbor_index])
+            .filter(|particle| !particle.is_ghost && particle.density > 0.0)
+            .fold((Vec2::ZERO, 0.0), |(velocity, total_weight), particle| {
+                let distance = point.distance(particle.predicted_position);
+                let weight =
+                    mass / particle.density * Self::density_kernel(smoothing_radius, distance);
+                (velocity + particle.velocity * weight, total_weight + weight)
+            });
+
+        (total_weight > 0.0).then(|| velocity / total_weight)
+    }
+
     pub fn calculate_viscosity_from_neighbors(
         current_index: usize,
         neighbor_indices: &[usize],
@@ -172,6 +197,37 @@ impl Physics {
         viscosity_force * viscosity_strength
     }
 
+    /// The curl of the SPH-interpolated velocity field at a particle. Positive
+    /// values rotate from +x towards +y, which is clockwise on screen.
+    pub fn calculate_vorticity_from_neighbors(
+        current_index: usize,
+        neighbor_indices: &[usize],
+        particles: &[Particle],
+        mass: f32,
+        smoothing_radius: f32,
+    ) -> f32 {
+        let current_particle = &particles[current_index];
+        let current_particle_pos = current_particle.predicted_position;
+
+        neighbor_indices
+            .iter()
+            .fold(0.0, |vorticity, &other_index| {
+                let other_particle = &particles[other_index];
+                let offset = current_particle_pos - other_particle.predicted_position;
+                let distance = offset.length();
+                if other_index == current_index || distance == 0.0 || other_particle.density <= 0.0
+                {
+                    return vorticity;
+                }
+
+                let kernel_gradient =
+                    offset / distance * Self::density_kernel_derivative(smoothing_radius, distance);
+                let relative_velocity = current_particle.velocity - other_particle.velocity;
+                vorticity
+                    + mass / other_particle.density * relative_velocity.perp_dot(kernel_gradient)
+            })
+    }
+
     pub fn calculate_pressure_force_on_particle(
         current_index: usize,
         neighbor_indices: &[usize],
@@ -179,18 +235,19 @@ impl Physics {
         mass: f32,
         radius: f32,
         config: &Config,
+        step_seed: u64,
     ) -> Vec2 {
         let mut pressure_force = Vec2::ZERO;
         let current_particle = &particles[current_index];
         let current_particle_pos = current_particle.predicted_position;
         let current_particle_density = current_particle.density;
 
-        for &other_particle in neighbor_indices {
-            if other_particle == current_index {
+        for &other_index in neighbor_indices {
+            if other_index == current_index {
                 continue;
             }
 
-            let other_particle = &particles[other_particle];
+            let other_particle = &particles[other_index];
             let distance = current_particle_pos.distance(other_particle.predicted_position);
             if distance == 0.0 || distance > radius {
                 continue;
@@ -200,7 +257,7 @@ impl Physics {
                 (other_particle.predicted_position - current_particle_pos).normalize_or_zero();
 
             if direction == Vec2::ZERO {
-                let angle = rand::gen_range(0.0, 2.0 * PI);
+                let angle = rng::hash_to_unit(step_seed, current_index, other_index) * 2.0 * PI;
                 direction = Vec2::new(angle.cos(), angle.sin());
             }
             let density_slope = Self::density_kernel_derivative(radius, distance);
@@ -0,0 +1,106 @@
+use fluid_simulation::profiler::{Phase, PhaseTimings};
+use macroquad::prelude::*;
+
+/// How often the shown averages are refreshed, in seconds.
+const REFRESH_INTERVAL: f32 = 0.5;
+const WIDTH: f32 = 280.0;
+const LINE_HEIGHT: f32 = 18.0;
+const BAR_WIDTH: f32 = 100.0;
+const BACKGROUND_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.6);
+const BAR_COLOR: Color = Color::new(0.3, 0.7, 1.0, 0.8);
+
+/// Per-phase timings averaged per frame, toggled with F7.
+pub struct ProfileHud {
+    pub visible: bool,
+    window: PhaseTimings,
+    window_time: f32,
+    window_frames: u32,
+    /// Averages of the last complete window, in milliseconds per frame.
+    phase_times: [f32; Phase::ALL.len()],
+    frame_time: f32,
+}
+
+impl ProfileHud {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            window: PhaseTimings::default(),
+            window_time: 0.0,
+            window_frames: 0,
+            phase_times: [0.0; Phase::ALL.len()],
+            frame_time: 0.0,
+        }
+    }
+
+    pub fn handle_input(&mut self) {
+        if is_key_pressed(KeyCode::F7) {
+            self.visible = !self.visible;
+        }
+    }
+
+    /// Adds the timings of a frame that took `frame_time` seconds.
+    pub fn record(&mut self, frame_time: f32, timings: &PhaseTimings) {
+        self.window.add_all(timings);
+        self.window_time += frame_time;
+        self.window_frames += 1;
+
+        if self.window_time >= REFRESH_INTERVAL {
+            let frames = self.window_frames as f32;
+            for (average, phase) in self.phase_times.iter_mut().zip(Phase::ALL) {
+                *average = self.window.get(phase).as_secs_f32() * 1000.0 / frames;
+            }
+            self.frame_time = self.window_time * 1000.0 / frames;
+            self.window = PhaseTimings::default();
+            self.window_time = 0.0;
+            self.window_frames = 0;
+        }
+    }
+
+    /// Draws the timings below the FPS counter in the top right corner, with
+    /// bars scaled to the frame time.
+    pub fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        let x = screen_width() - WIDTH - 10.0;
+        let y = 30.0;
+        let line_count = Phase::ALL.len() + 2;
+        draw_rectangle(
+            x,
+            y,
+            WIDTH,
+            line_count as f32 * LINE_HEIGHT + 8.0,
+            BACKGROUND_COLOR,
+        );
+
+        let draw_row = |index: usize, label: &str, time: f32, color: Color| {
+            let baseline = y + (index + 1) as f32 * LINE_HEIGHT;
+            draw_text(label, x + 6.0, baseline, 18.0, color);
+            draw_text(
+                &format!("{:6.2} ms", time),
+                x + 110.0,
+                baseline,
+                18.0,
+                color,
+            );
+            baseline
+        };
+
+        for (index, (phase, &time)) in Phase::ALL.iter().zip(&self.phase_times).enumerate() {
+            let baseline = draw_row(index, phase.label(), time, WHITE);
+            if self.frame_time > 0.0 {
+                let width = (time / self.frame_time).min(1.0) * BAR_WIDTH;
+                draw_rectangle(
+                    x + WIDTH - BAR_WIDTH - 6.0,
+                    baseline - 10.0,
+                    width,
+                    10.0,
+                    BAR_COLOR,
+                );
+            }
+        }
+        let total = self.phase_times.iter().sum();
+        draw_row(Phase::ALL.len(), "total", total, YELLOW);
+        draw_row(Phase::ALL.len() + 1, "frame", self.frame_time, YELLOW);
+    }
+}
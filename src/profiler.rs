@@ -0,0 +1,152 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// A part of the work done per step or frame that is timed separately.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Spawn,
+    Predict,
+    SpatialGrid,
+    Density,
+    Forces,
+    Integration,
+    Interaction,
+    Collision,
+    Render,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 9] = [
+        Phase::Spawn,
+        Phase::Predict,
+        Phase::SpatialGrid,
+        Phase::Density,
+        Phase::Forces,
+        Phase::Integration,
+        Phase::Interaction,
+        Phase::Collision,
+        Phase::Render,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Phase::Spawn => "spawn",
+            Phase::Predict => "predict",
+            Phase::SpatialGrid => "spatial_grid",
+            Phase::Density => "density",
+            Phase::Forces => "forces",
+            Phase::Integration => "integration",
+            Phase::Interaction => "interaction",
+            Phase::Collision => "collision",
+            Phase::Render => "render",
+        }
+    }
+}
+
+/// Time spent in each `Phase`, summed until the timings are taken.
+#[derive(Debug, Clone, Default)]
+pub struct PhaseTimings {
+    durations: [Duration; Phase::ALL.len()],
+}
+
+impl PhaseTimings {
+    /// Runs `f` and adds the time it took to `phase`.
+    pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.add(phase, start.elapsed());
+        result
+    }
+
+    pub fn add(&mut self, phase: Phase, duration: Duration) {
+        self.durations[phase as usize] += duration;
+    }
+
+    pub fn add_all(&mut self, other: &PhaseTimings) {
+        for phase in Phase::ALL {
+            self.add(phase, other.get(phase));
+        }
+    }
+
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.durations[phase as usize]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.durations.iter().sum()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TraceFormat {
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Writes one row of phase timings, in milliseconds, per frame or step.
+pub struct TraceWriter {
+    writer: BufWriter<fs::File>,
+    format: TraceFormat,
+}
+
+impl TraceWriter {
+    pub fn create(path: &Path, format: TraceFormat) -> io::Result<Self> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        if format == TraceFormat::Csv {
+            write!(writer, "index,step,particles")?;
+            for phase in Phase::ALL {
+                write!(writer, ",{}_ms", phase.label())?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(Self { writer, format })
+    }
+
+    /// `index` is the frame or step the row is for and `step` the
+    /// simulation's step count after it.
+    pub fn write_row(
+        &mut self,
+        index: u64,
+        step: u64,
+        particle_count: usize,
+        timings: &PhaseTimings,
+    ) -> io::Result<()> {
+        let milliseconds = |phase: Phase| timings.get(phase).as_secs_f64() * 1000.0;
+        match self.format {
+            TraceFormat::Csv => {
+                write!(self.writer, "{},{},{}", index, step, particle_count)?;
+                for phase in Phase::ALL {
+                    write!(self.writer, ",{:.4}", milliseconds(phase))?;
+                }
+                writeln!(self.writer)
+            }
+            TraceFormat::Json => {
+                // Written by hand to keep the keys in the same order as the
+                // CSV columns
+                write!(
+                    self.writer,
+                    "{{\"index\":{},\"step\":{},\"particles\":{}",
+                    index, step, particle_count
+                )?;
+                for phase in Phase::ALL {
+                    write!(
+                        self.writer,
+                        ",\"{}_ms\":{:.4}",
+                        phase.label(),
+                        milliseconds(phase)
+                    )?;
+                }
+                writeln!(self.writer, "}}")
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
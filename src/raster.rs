@@ -0,0 +1,538 @@
+use clap::ValueEnum;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use crate::{
+    boundary::Boundary,
+    colormap::{self, ParticleColors},
+    config::Config,
+    fluid::Fluid,
+    font,
+    surface::Surface,
+    velocity_field::{VelocityField, VelocityFieldMode},
+};
+
+pub const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+pub const BOUNDARY_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const GRID_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 0.25];
+pub const GHOST_COLOR: [f32; 4] = [1.0, 0.3, 0.8, 0.8];
+pub const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const NEIGHBOR_CELL_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 0.12];
+pub const NEIGHBOR_COLOR: [f32; 4] = [0.2, 1.0, 0.2, 1.0];
+/// Occupancy counts are hidden when cells are smaller than this on screen.
+pub const MIN_COUNT_CELL_SIZE: f32 = 24.0;
+
+/// How the fluid is drawn.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum RenderMode {
+    Particles,
+    Surface,
+    SurfaceAndParticles,
+}
+
+impl RenderMode {
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Particles => RenderMode::Surface,
+            RenderMode::Surface => RenderMode::SurfaceAndParticles,
+            RenderMode::SurfaceAndParticles => RenderMode::Particles,
+        }
+    }
+
+    pub fn draws_particles(self) -> bool {
+        self != RenderMode::Surface
+    }
+
+    pub fn draws_surface(self) -> bool {
+        self != RenderMode::Particles
+    }
+}
+
+/// What `render_frame` draws, and at which size.
+#[derive(Debug, Clone)]
+pub struct FrameOptions {
+    pub width: u32,
+    pub height: u32,
+    pub render_mode: RenderMode,
+    pub show_grid: bool,
+    pub show_ghosts: bool,
+    pub velocity_field: VelocityFieldMode,
+    /// Draws the particle count of each occupied grid cell.
+    pub show_occupancy: bool,
+    /// Highlights this particle and its neighbours the way hovering it with
+    /// the neighbour overlay does. Ignored if there is no such particle.
+    pub highlighted_particle: Option<usize>,
+    pub show_legend: bool,
+}
+
+/// An RGBA image with 8 bits per channel that shapes are blended into.
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, background: [f32; 4]) -> Self {
+        let pixel = background.map(to_u8);
+        Self {
+            width,
+            height,
+            pixels: pixel.repeat(width as usize * height as usize),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Row-major RGBA bytes, top row first.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Blends `color` over the pixel at `(x, y)` with its alpha scaled by
+    /// `coverage`.
+    fn blend_pixel(&mut self, x: i64, y: i64, color: [f32; 4], coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let alpha = color[3] * coverage.clamp(0.0, 1.0);
+        if alpha <= 0.0 {
+            return;
+        }
+
+        let offset = (y as usize * self.width as usize + x as usize) * 4;
+        let pixel = &mut self.pixels[offset..offset + 4];
+        for channel in 0..3 {
+            let destination = pixel[channel] as f32 / 255.0;
+            pixel[channel] = to_u8(color[channel] * alpha + destination * (1.0 - alpha));
+        }
+        let destination_alpha = pixel[3] as f32 / 255.0;
+        pixel[3] = to_u8(alpha + destination_alpha * (1.0 - alpha));
+    }
+
+    /// Pixel bounds `(min_x, min_y, max_x, max_y)` covering `min..max`,
+    /// clipped to the canvas.
+    fn pixel_bounds(&self, min: Vec2, max: Vec2) -> (i64, i64, i64, i64) {
+        (
+            (min.x.floor() as i64).max(0),
+            (min.y.floor() as i64).max(0),
+            (max.x.ceil() as i64).min(self.width as i64 - 1),
+            (max.y.ceil() as i64).min(self.height as i64 - 1),
+        )
+    }
+
+    /// Fills a circle with an anti-aliased edge.
+    pub fn fill_circle(&mut self, center: Vec2, radius: f32, color: [f32; 4]) {
+        let extent = Vec2::splat(radius + 1.0);
+        let (min_x, min_y, max_x, max_y) = self.pixel_bounds(center - extent, center + extent);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let pixel_center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let coverage = radius + 0.5 - pixel_center.distance(center);
+                self.blend_pixel(x, y, color, coverage);
+            }
+        }
+    }
+
+    /// Draws an anti-aliased line segment `thickness` pixels wide.
+    pub fn draw_line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: [f32; 4]) {
+        let half_thickness = thickness / 2.0;
+        let extent = Vec2::splat(half_thickness + 1.0);
+        let (min_x, min_y, max_x, max_y) =
+            self.pixel_bounds(start.min(end) - extent, start.max(end) + extent);
+        let segment = end - start;
+        let length_squared = segment.length_squared();
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let pixel_center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let t = if length_squared > 0.0 {
+                    ((pixel_center - start).dot(segment) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let distance = pixel_center.distance(start + segment * t);
+                self.blend_pixel(x, y, color, half_thickness + 0.5 - distance);
+            }
+        }
+    }
+
+    /// Fills a triangle, sampling at pixel centres.
+    ///
+    /// Pixels exactly on an edge belong to only one side of it, so triangles
+    /// sharing an edge (such as the pieces of a `Surface`) do not blend twice.
+    pub fn fill_triangle(&mut self, vertices: [Vec2; 3], color: [f32; 4]) {
+        let [a, b, c] = vertices;
+        let area = (b - a).perp_dot(c - a);
+        if area == 0.0 {
+            return;
+        }
+        // Wind every triangle the same way so shared edges run in opposite
+        // directions in the two triangles
+        let [a, b, c] = if area > 0.0 { [a, b, c] } else { [a, c, b] };
+        let edges = [(a, b), (b, c), (c, a)];
+        let owns_edge = |(start, end): (Vec2, Vec2)| {
+            let direction = end - start;
+            direction.y < 0.0 || (direction.y == 0.0 && direction.x > 0.0)
+        };
+
+        let (min_x, min_y, max_x, max_y) = self.pixel_bounds(a.min(b).min(c), a.max(b).max(c));
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let pixel_center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let is_inside = edges.iter().all(|&(start, end)| {
+                    let side = (end - start).perp_dot(pixel_center - start);
+                    side > 0.0 || (side == 0.0 && owns_edge((start, end)))
+                });
+                if is_inside {
+                    self.blend_pixel(x, y, color, 1.0);
+                }
+            }
+        }
+    }
+
+    /// Fills the pixels whose centres lie in `min..max`.
+    pub fn fill_rect(&mut self, min: Vec2, max: Vec2, color: [f32; 4]) {
+        let (min_x, min_y, max_x, max_y) = self.pixel_bounds(min, max);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let pixel_center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if pixel_center.cmpge(min).all() && pixel_center.cmplt(max).all() {
+                    self.blend_pixel(x, y, color, 1.0);
+                }
+            }
+        }
+    }
+
+    /// Draws an anti-aliased circle outline `thickness` pixels wide.
+    pub fn draw_circle_outline(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        thickness: f32,
+        color: [f32; 4],
+    ) {
+        let half_thickness = thickness / 2.0;
+        let extent = Vec2::splat(radius + half_thickness + 1.0);
+        let (min_x, min_y, max_x, max_y) = self.pixel_bounds(center - extent, center + extent);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let pixel_center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let distance = (pixel_center.distance(center) - radius).abs();
+                self.blend_pixel(x, y, color, half_thickness + 0.5 - distance);
+            }
+        }
+    }
+
+    /// Draws `text` in a 5x7 bitmap font with its top left corner at
+    /// `position`, each font pixel `scale` pixels wide.
+    pub fn draw_text(&mut self, text: &str, position: Vec2, scale: u32, color: [f32; 4]) {
+        let pixel_size = scale as f32;
+        let position = position.round();
+        for (index, c) in text.chars().enumerate() {
+            let Some(rows) = font::glyph(c) else {
+                continue;
+            };
+            let glyph_position =
+                position + Vec2::new((index as u32 * font::ADVANCE) as f32 * pixel_size, 0.0);
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..font::GLYPH_WIDTH {
+                    if bits & (1 << (font::GLYPH_WIDTH - 1 - column)) != 0 {
+                        let min =
+                            glyph_position + Vec2::new(column as f32, row as f32) * pixel_size;
+                        self.fill_rect(min, min + Vec2::splat(pixel_size), color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The size of `text` drawn by `draw_text` at `scale`.
+    pub fn text_size(text: &str, scale: u32) -> Vec2 {
+        let width = (text.chars().count() as u32 * font::ADVANCE).saturating_sub(1);
+        Vec2::new(width as f32, font::GLYPH_HEIGHT as f32) * scale as f32
+    }
+
+    /// Draws the outline of the rectangle `min..max`, one pixel wide.
+    fn stroke_rect(&mut self, min: Vec2, max: Vec2, color: [f32; 4]) {
+        let top_right = Vec2::new(max.x, min.y);
+        let bottom_left = Vec2::new(min.x, max.y);
+        for (start, end) in [
+            (min, top_right),
+            (top_right, max),
+            (max, bottom_left),
+            (bottom_left, min),
+        ] {
+            self.draw_line(start, end, 1.0, color);
+        }
+    }
+
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        let writer = BufWriter::new(fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .map_err(io::Error::other)
+    }
+}
+
+/// Renders the boundary, fluid and the overlays selected in `options` the way
+/// the interactive view does at its default zoom, with the domain scaled to
+/// fit the image and centred in it.
+pub fn render_frame(
+    fluid: &Fluid,
+    boundary: &Boundary,
+    config: &Config,
+    options: &FrameOptions,
+) -> Canvas {
+    let mut canvas = Canvas::new(options.width, options.height, BACKGROUND_COLOR);
+
+    let image_size = Vec2::new(options.width as f32, options.height as f32);
+    let domain_size = boundary.domain_size();
+    let scale = (image_size / domain_size).min_element();
+    let offset = (image_size - domain_size * scale) / 2.0;
+    let to_pixels = |point: Vec2| point * scale + offset;
+
+    canvas.stroke_rect(
+        to_pixels(boundary.pos),
+        to_pixels(boundary.pos + Vec2::new(boundary.width, boundary.height)),
+        BOUNDARY_COLOR,
+    );
+
+    if options.show_grid
+        && let Some(first_cell) = fluid.grid.first()
+    {
+        // Each line once, shared cell edges would otherwise blend twice
+        let (cols, rows) = fluid.grid_dimensions();
+        let origin = to_pixels(first_cell.position);
+        let cell_size = first_cell.grid_size * scale;
+        let extent = Vec2::new(cols as f32, rows as f32) * cell_size;
+        for col in 0..=cols {
+            let x = origin.x + col as f32 * cell_size;
+            canvas.draw_line(
+                Vec2::new(x, origin.y),
+                Vec2::new(x, origin.y + extent.y),
+                1.0,
+                GRID_COLOR,
+            );
+        }
+        for row in 0..=rows {
+            let y = origin.y + row as f32 * cell_size;
+            canvas.draw_line(
+                Vec2::new(origin.x, y),
+                Vec2::new(origin.x + extent.x, y),
+                1.0,
+                GRID_COLOR,
+            );
+        }
+    }
+
+    if options.render_mode.draws_surface() {
+        let surface = Surface::extract(fluid, boundary, config);
+        for triangle in &surface.triangles {
+            canvas.fill_triangle(triangle.map(to_pixels), config.surface.fill_color);
+        }
+        for &[start, end] in &surface.outline {
+            canvas.draw_line(
+                to_pixels(start),
+                to_pixels(end),
+                config.surface.outline_width * scale,
+                config.surface.outline_color,
+            );
+        }
+    }
+
+    let colors = ParticleColors::new(fluid, config);
+    if options.render_mode.draws_particles() {
+        for (index, particle) in fluid.particles.iter().enumerate() {
+            let color = if particle.is_ghost {
+                if !options.show_ghosts {
+                    continue;
+                }
+                GHOST_COLOR
+            } else {
+                let [r, g, b] = colors.color(index);
+                [r, g, b, 1.0]
+            };
+            canvas.fill_circle(to_pixels(particle.position), particle.radius * scale, color);
+        }
+    }
+
+    let velocity_field = VelocityField::compute(options.velocity_field, fluid, boundary, config);
+    let field_color = options.velocity_field.color();
+    for streamline in &velocity_field.streamlines {
+        for segment in streamline.windows(2) {
+            canvas.draw_line(
+                to_pixels(segment[0]),
+                to_pixels(segment[1]),
+                1.0,
+                field_color,
+            );
+        }
+    }
+    for arrow in &velocity_field.arrows {
+        canvas.draw_line(
+            to_pixels(arrow.start),
+            to_pixels(arrow.end),
+            1.0,
+            field_color,
+        );
+        canvas.fill_triangle(arrow.head.map(to_pixels), field_color);
+    }
+
+    let highlighted_particle = options
+        .highlighted_particle
+        .filter(|&index| index < fluid.particles.len());
+    if let Some(index) = highlighted_particle {
+        draw_neighbors(&mut canvas, fluid, config, index, scale, &to_pixels);
+    }
+
+    if options.show_occupancy {
+        for cell in &fluid.grid {
+            let cell_size = cell.grid_size * scale;
+            if cell.particles.is_empty() || cell_size < MIN_COUNT_CELL_SIZE {
+                continue;
+            }
+            let text = cell.particles.len().to_string();
+            let text_scale = ((cell_size * 0.4).min(32.0) / font::GLYPH_HEIGHT as f32).max(1.0);
+            let text_size = Canvas::text_size(&text, text_scale as u32);
+            let position = to_pixels(cell.position) + (Vec2::splat(cell_size) - text_size) / 2.0;
+            canvas.draw_text(&text, position, text_scale as u32, TEXT_COLOR);
+        }
+    }
+
+    if let Some(index) = highlighted_particle {
+        let particle = &fluid.particles[index];
+        let neighbor_count = fluid
+            .neighbors_in_radius(index, config.smoothing_radius)
+            .len();
+        let text = format!(
+            "Particle {}{}: {} neighbours, density {:.1}, near density {:.1}",
+            index,
+            if particle.is_ghost { " (ghost)" } else { "" },
+            neighbor_count,
+            particle.density,
+            particle.near_density
+        );
+        let position = Vec2::new(10.0, image_size.y - 10.0 - Canvas::text_size(&text, 2).y);
+        canvas.draw_text(&text, position, 2, NEIGHBOR_COLOR);
+    }
+
+    if options.show_legend {
+        draw_legend(&mut canvas, &colors, image_size - Vec2::splat(10.0));
+    }
+
+    canvas
+}
+
+/// Shades the 3x3 candidate cells of the particle at `index`, rings its
+/// neighbours and outlines its smoothing radius.
+fn draw_neighbors(
+    canvas: &mut Canvas,
+    fluid: &Fluid,
+    config: &Config,
+    index: usize,
+    scale: f32,
+    to_pixels: &impl Fn(Vec2) -> Vec2,
+) {
+    let particle = &fluid.particles[index];
+    let (grid_x, grid_y) = fluid.get_grid_coords(particle.predicted_position);
+    let (grid_cols, grid_rows) = fluid.grid_dimensions();
+
+    for ny in grid_y.saturating_sub(1)..=(grid_y + 1).min(grid_rows - 1) {
+        for nx in grid_x.saturating_sub(1)..=(grid_x + 1).min(grid_cols - 1) {
+            if let Some(cell) = fluid.grid.get(fluid.get_grid_index(nx, ny)) {
+                let min = to_pixels(cell.position);
+                let max = min + Vec2::splat(cell.grid_size * scale);
+                canvas.fill_rect(min, max, NEIGHBOR_CELL_COLOR);
+            }
+        }
+    }
+
+    for other in fluid.neighbors_in_radius(index, config.smoothing_radius) {
+        let other = &fluid.particles[other];
+        canvas.draw_circle_outline(
+            to_pixels(other.position),
+            (other.radius + 1.0) * scale,
+            1.0,
+            NEIGHBOR_COLOR,
+        );
+    }
+
+    canvas.draw_circle_outline(
+        to_pixels(particle.predicted_position),
+        config.smoothing_radius * scale,
+        1.0,
+        NEIGHBOR_COLOR,
+    );
+    canvas.fill_circle(
+        to_pixels(particle.position),
+        (particle.radius + 1.0) * scale,
+        TEXT_COLOR,
+    );
+}
+
+/// Draws the palette as a horizontal bar with the quantity, palette and range
+/// around it, with the bottom right corner at `corner`, like the legend of
+/// the interactive view.
+fn draw_legend(canvas: &mut Canvas, colors: &ParticleColors, corner: Vec2) {
+    const BAR_SIZE: Vec2 = Vec2::new(200.0, 12.0);
+    const SEGMENTS: usize = 64;
+    const TEXT_SCALE: u32 = 2;
+
+    let bar_position = corner - Vec2::new(BAR_SIZE.x, BAR_SIZE.y + 30.0);
+    let segment_width = BAR_SIZE.x / SEGMENTS as f32;
+    for segment in 0..SEGMENTS {
+        let t = segment as f32 / (SEGMENTS - 1) as f32;
+        let [r, g, b] = colors.palette.sample(t);
+        let min = bar_position + Vec2::new(segment as f32 * segment_width, 0.0);
+        canvas.fill_rect(
+            min,
+            min + Vec2::new(segment_width, BAR_SIZE.y),
+            [r, g, b, 1.0],
+        );
+    }
+    canvas.stroke_rect(bar_position, bar_position + BAR_SIZE, TEXT_COLOR);
+
+    let title = format!("{} ({})", colors.color_by.label(), colors.palette.label());
+    let title_height = Canvas::text_size(&title, TEXT_SCALE).y;
+    canvas.draw_text(
+        &title,
+        bar_position - Vec2::new(0.0, 6.0 + title_height),
+        TEXT_SCALE,
+        TEXT_COLOR,
+    );
+
+    let label_y = bar_position.y + BAR_SIZE.y + 4.0;
+    let min_label = colormap::format_legend_value(colors.min);
+    let max_label = colormap::format_legend_value(colors.max);
+    canvas.draw_text(
+        &min_label,
+        Vec2::new(bar_position.x, label_y),
+        TEXT_SCALE,
+        TEXT_COLOR,
+    );
+    let max_width = Canvas::text_size(&max_label, TEXT_SCALE).x;
+    canvas.draw_text(
+        &max_label,
+        Vec2::new(corner.x - max_width, label_y),
+        TEXT_SCALE,
+        TEXT_COLOR,
+    );
+}
+
+fn to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
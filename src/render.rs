@@ -0,0 +1,157 @@
+use fluid_simulation::{
+    boundary::Boundary,
+    colormap::{self, ParticleColors},
+    config::SurfaceConfig,
+    fluid::Fluid,
+    grid::GridCell,
+    particle::Particle,
+    raster,
+    surface::Surface,
+    velocity_field::{VelocityField, VelocityFieldMode},
+};
+use macroquad::prelude::*;
+
+pub fn draw_boundary(boundary: &Boundary) {
+    draw_rectangle_lines(
+        boundary.pos.x,
+        boundary.pos.y,
+        boundary.width,
+        boundary.height,
+        1.,
+        Color::from(raster::BOUNDARY_COLOR),
+    );
+}
+
+/// Draws the fluid particles coloured by `colors`, and the ghost particles in
+/// a single distinct colour if `show_ghosts` is set.
+pub fn draw_fluid(fluid: &Fluid, colors: &ParticleColors, show_ghosts: bool) {
+    for (index, particle) in fluid.particles.iter().enumerate() {
+        if particle.is_ghost {
+            if show_ghosts {
+                draw_particle(particle, Color::from(raster::GHOST_COLOR));
+            }
+        } else {
+            let [r, g, b] = colors.color(index);
+            draw_particle(particle, Color::new(r, g, b, 1.0));
+        }
+    }
+}
+
+pub fn draw_surface(surface: &Surface, config: &SurfaceConfig) {
+    let [r, g, b, a] = config.fill_color;
+    let fill_color = Color::new(r, g, b, a);
+    for &[p1, p2, p3] in &surface.triangles {
+        draw_triangle(p1, p2, p3, fill_color);
+    }
+
+    let [r, g, b, a] = config.outline_color;
+    let outline_color = Color::new(r, g, b, a);
+    for &[start, end] in &surface.outline {
+        draw_line(
+            start.x,
+            start.y,
+            end.x,
+            end.y,
+            config.outline_width,
+            outline_color,
+        );
+    }
+}
+
+pub fn draw_grid(fluid: &Fluid) {
+    for grid_box in &fluid.grid {
+        draw_grid_cell(grid_box);
+    }
+}
+
+pub fn draw_particle(particle: &Particle, color: Color) {
+    draw_circle(
+        particle.position.x,
+        particle.position.y,
+        particle.radius,
+        color,
+    );
+}
+
+/// Draws the palette as a horizontal bar with the quantity, palette and range
+/// below it, in screen space with the bottom right corner at `corner`.
+pub fn draw_legend(colors: &ParticleColors, corner: Vec2) {
+    const BAR_SIZE: Vec2 = vec2(200.0, 12.0);
+    const SEGMENTS: usize = 64;
+
+    let bar_position = corner - vec2(BAR_SIZE.x, BAR_SIZE.y + 30.0);
+    let segment_width = BAR_SIZE.x / SEGMENTS as f32;
+    for segment in 0..SEGMENTS {
+        let t = segment as f32 / (SEGMENTS - 1) as f32;
+        let [r, g, b] = colors.palette.sample(t);
+        draw_rectangle(
+            bar_position.x + segment as f32 * segment_width,
+            bar_position.y,
+            segment_width + 0.5,
+            BAR_SIZE.y,
+            Color::new(r, g, b, 1.0),
+        );
+    }
+    draw_rectangle_lines(
+        bar_position.x,
+        bar_position.y,
+        BAR_SIZE.x,
+        BAR_SIZE.y,
+        1.0,
+        WHITE,
+    );
+
+    let title = format!("{} ({})", colors.color_by.label(), colors.palette.label());
+    draw_text(&title, bar_position.x, bar_position.y - 6.0, 20.0, WHITE);
+
+    let label_y = bar_position.y + BAR_SIZE.y + 16.0;
+    let min_label = colormap::format_legend_value(colors.min);
+    let max_label = colormap::format_legend_value(colors.max);
+    draw_text(&min_label, bar_position.x, label_y, 18.0, WHITE);
+    let max_width = measure_text(&max_label, None, 18, 1.0).width;
+    draw_text(&max_label, corner.x - max_width, label_y, 18.0, WHITE);
+}
+
+/// Draws the streamlines and arrows of `field` in world space.
+pub fn draw_velocity_field(field: &VelocityField, mode: VelocityFieldMode) {
+    let color = Color::from(mode.color());
+    for streamline in &field.streamlines {
+        for segment in streamline.windows(2) {
+            draw_line(
+                segment[0].x,
+                segment[0].y,
+                segment[1].x,
+                segment[1].y,
+                1.0,
+                color,
+            );
+        }
+    }
+    for arrow in &field.arrows {
+        draw_line(
+            arrow.start.x,
+            arrow.start.y,
+            arrow.end.x,
+            arrow.end.y,
+            1.0,
+            color,
+        );
+        let [tip, left, right] = arrow.head;
+        draw_triangle(tip, left, right, color);
+    }
+}
+
+pub fn draw_grid_cell(cell: &GridCell) {
+    let grid_color = Color::from(raster::GRID_COLOR);
+    let grid_width = cell.grid_size;
+    let grid_height = cell.grid_size;
+
+    draw_rectangle_lines(
+        cell.position.x,
+        cell.position.y,
+        grid_width,
+        grid_height,
+        1.0,
+        grid_color,
+    );
+}
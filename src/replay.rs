@@ -0,0 +1,208 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use crate::{
+    checkpoint::Checkpoint,
+    config::{Config, FluidType, InteractionType},
+    simulation::Simulation,
+};
+
+/// A user input that changes how the simulation evolves.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    Interact {
+        point: Vec2,
+        interaction_type: InteractionType,
+    },
+    ClearInteraction,
+    SetFluidType(FluidType),
+    TogglePause,
+    /// Steps taken one at a time or in a burst while paused.
+    Step(u32),
+    /// Runtime parameters changed from the panel or by reloading the config
+    /// file, applied with `Simulation::apply_tunable_config`.
+    ConfigChanged(Box<Config>),
+}
+
+/// An input event and the step count of the simulation when it happened. It
+/// takes effect from step `step + 1` on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedEvent {
+    pub step: u64,
+    pub event: InputEvent,
+}
+
+/// A recorded session: the state it started from and every input since.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub initial_state: Checkpoint,
+    pub starts_paused: bool,
+    pub events: Vec<RecordedEvent>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Format(serde_json::Error),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "{}", e),
+            ReplayError::Format(e) => write!(f, "invalid replay: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ReplayError {
+    fn from(e: serde_json::Error) -> Self {
+        ReplayError::Format(e)
+    }
+}
+
+impl Replay {
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        let writer = BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let reader = BufReader::new(fs::File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// Collects input events into a `Replay`, starting from the simulation's
+/// current state.
+pub struct Recorder {
+    replay: Replay,
+    /// The interaction currently applied, so that holding the mouse still
+    /// does not record an event every frame.
+    interaction: Option<(Vec2, InteractionType)>,
+}
+
+impl Recorder {
+    pub fn start(simulation: &Simulation, is_paused: bool) -> Self {
+        Self {
+            replay: Replay {
+                initial_state: simulation.to_checkpoint(),
+                starts_paused: is_paused,
+                events: Vec::new(),
+            },
+            interaction: None,
+        }
+    }
+
+    pub fn record(&mut self, step: u64, event: InputEvent) {
+        match event {
+            InputEvent::Interact {
+                point,
+                interaction_type,
+            } => {
+                if self.interaction == Some((point, interaction_type)) {
+                    return;
+                }
+                self.interaction = Some((point, interaction_type));
+            }
+            InputEvent::ClearInteraction => {
+                if self.interaction.is_none() {
+                    return;
+                }
+                self.interaction = None;
+            }
+            InputEvent::SetFluidType(_)
+            | InputEvent::TogglePause
+            | InputEvent::Step(_)
+            | InputEvent::ConfigChanged(_) => {}
+        }
+        self.replay.events.push(RecordedEvent { step, event });
+    }
+
+    pub fn event_count(&self) -> usize {
+        self.replay.events.len()
+    }
+
+    pub fn finish(self) -> Replay {
+        self.replay
+    }
+}
+
+/// Re-applies the events of a `Replay` to a simulation started from its
+/// `initial_state`.
+pub struct Player {
+    starts_paused: bool,
+    events: Vec<RecordedEvent>,
+    next_event: usize,
+}
+
+impl Player {
+    /// Returns the player and the state to start the simulation from.
+    pub fn new(replay: Replay) -> (Self, Checkpoint) {
+        let player = Self {
+            starts_paused: replay.starts_paused,
+            events: replay.events,
+            next_event: 0,
+        };
+        (player, replay.initial_state)
+    }
+
+    pub fn starts_paused(&self) -> bool {
+        self.starts_paused
+    }
+
+    /// Applies every event recorded at or before the simulation's current
+    /// step count, including the events after recorded steps, which are
+    /// taken here. Call before each step. Returns how many pause toggles were
+    /// among them, which only matter to an interactive front end.
+    pub fn apply_due(&mut self, simulation: &mut Simulation) -> u32 {
+        let mut pause_toggles = 0;
+        while let Some(recorded) = self.events.get(self.next_event) {
+            if recorded.step > simulation.step_count() {
+                break;
+            }
+            match &recorded.event {
+                InputEvent::Interact {
+                    point,
+                    interaction_type,
+                } => simulation.set_interaction(*point, *interaction_type),
+                InputEvent::ClearInteraction => simulation.clear_interaction(),
+                InputEvent::SetFluidType(fluid_type) => {
+                    // Switching only fails for configs that failed when
+                    // recording too, in which case it was not recorded
+                    let _ = simulation.set_fluid_type(*fluid_type);
+                }
+                InputEvent::TogglePause => pause_toggles += 1,
+                InputEvent::Step(steps) => {
+                    for _ in 0..*steps {
+                        simulation.step();
+                    }
+                }
+                InputEvent::ConfigChanged(config) => simulation.apply_tunable_config(config),
+            }
+            self.next_event += 1;
+        }
+        pause_toggles
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.events.len()
+    }
+
+    pub fn progress(&self) -> (usize, usize) {
+        (self.next_event, self.events.len())
+    }
+}
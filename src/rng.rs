@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// Small seedable SplitMix64 generator.
+///
+/// Each simulation owns its own instance so that runs with the same seed are
+/// reproducible regardless of what else is using randomness in the process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.state)
+    }
+
+    /// Uniform value in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        unit_f32(self.next_u64())
+    }
+
+    pub fn gen_range(&mut self, low: f32, high: f32) -> f32 {
+        low + (high - low) * self.next_f32()
+    }
+}
+
+/// Stateless uniform value in `[0, 1)` derived from a seed and two indices.
+///
+/// Used from parallel code where sharing a generator between threads would
+/// make the result depend on scheduling order.
+pub fn hash_to_unit(seed: u64, a: usize, b: usize) -> f32 {
+    let hash = mix(seed ^ mix(((a as u64) << 32) ^ b as u64));
+    unit_f32(hash)
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn unit_f32(value: u64) -> f32 {
+    (value >> 40) as f32 / (1u64 << 24) as f32
+}
@@ -1,102 +1,241 @@
-use macroquad::prelude::*;
+use glam::Vec2;
+use std::mem;
+use std::ops::ControlFlow;
 
 use crate::{
     boundary::Boundary,
-    config::{Config, InteractionType},
+    checkpoint::Checkpoint,
+    config::{Config, ConfigError, FluidType, InteractionType},
     fluid::Fluid,
+    profiler::{Phase, PhaseTimings},
 };
 
 pub const DISTANCE_ZOOM: f32 = 1000.0;
 
 pub struct Simulation {
-    is_running: bool,
-    is_paused: bool,
     config: Config,
     fluid: Fluid,
     boundary: Boundary,
     click_point: Option<Vec2>,
     interaction_type: Option<InteractionType>,
+    accumulator: f32,
+    elapsed_time: f32,
+    step_count: u64,
+    timings: PhaseTimings,
 }
 
 impl Simulation {
     pub fn new(config: Config) -> Self {
-        let boundary = Boundary::new(config.boundary_damping, config.smoothing_radius);
+        let boundary = Boundary::new(
+            config.boundary_damping,
+            config.smoothing_radius,
+            Vec2::new(config.domain_width, config.domain_height),
+        );
         let fluid = Fluid::from_config(&config, &boundary);
 
         Self {
-            is_running: true,
-            is_paused: true,
             config,
             fluid,
             boundary,
             click_point: None,
             interaction_type: None,
+            accumulator: 0.0,
+            elapsed_time: 0.0,
+            step_count: 0,
+            timings: PhaseTimings::default(),
         }
     }
 
-    pub fn handle_input(&mut self) {
-        if is_key_pressed(KeyCode::Escape) {
-            self.is_running = false;
-        }
-        if is_key_pressed(KeyCode::Space) {
-            self.is_paused = !self.is_paused;
-        }
+    pub fn from_checkpoint(checkpoint: Checkpoint) -> Self {
+        let mut config = checkpoint.config;
+        config.adapt_to_fluid_type();
 
-        if is_mouse_button_down(MouseButton::Left) {
-            let mouse_pos = mouse_position();
-            self.click_point = Some(Vec2::new(mouse_pos.0, mouse_pos.1));
-            self.interaction_type = Some(InteractionType::Pull);
-        }
+        let mut fluid = checkpoint.fluid;
+        fluid.rebuild_grid();
 
-        if is_mouse_button_down(MouseButton::Right) {
-            let mouse_pos = mouse_position();
-            self.click_point = Some(Vec2::new(mouse_pos.0, mouse_pos.1));
-            self.interaction_type = Some(InteractionType::Push);
+        Self {
+            config,
+            fluid,
+            boundary: checkpoint.boundary,
+            click_point: None,
+            interaction_type: None,
+            accumulator: checkpoint.accumulator,
+            elapsed_time: checkpoint.elapsed_time,
+            step_count: checkpoint.step_count,
+            timings: PhaseTimings::default(),
         }
+    }
 
-        if is_mouse_button_released(MouseButton::Left) {
-            self.click_point = None;
-            self.interaction_type = None;
+    pub fn to_checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            config: self.config.clone(),
+            boundary: self.boundary.clone(),
+            fluid: self.fluid.clone(),
+            accumulator: self.accumulator,
+            elapsed_time: self.elapsed_time,
+            step_count: self.step_count,
         }
+    }
 
-        if is_mouse_button_released(MouseButton::Right) {
-            self.click_point = None;
-            self.interaction_type = None;
-        }
+    pub fn config(&self) -> &Config {
+        &self.config
     }
 
-    pub fn update(&mut self, delta_time: f32) {
-        if !self.is_running {
-            std::process::exit(0);
-        }
-        if self.is_paused {
-            return;
-        }
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    /// Applies the runtime-tunable parameters of `config`, see
+    /// `Config::apply_tunables`.
+    pub fn apply_tunable_config(&mut self, config: &Config) {
+        self.config.apply_tunables(config);
+        self.boundary.set_damping(self.config.boundary_damping);
+    }
+
+    /// Restarts the run from the current config, re-spawning the fluid as if
+    /// the simulation had just been created. Runtime parameter changes and the
+    /// current fluid type are kept.
+    pub fn reset(&mut self) {
+        self.fluid = Fluid::from_config(&self.config, &self.boundary);
+        self.accumulator = 0.0;
+        self.elapsed_time = 0.0;
+        self.step_count = 0;
+        self.clear_interaction();
+    }
 
-        self.fluid
-            .update(delta_time, self.config.gravity, &self.config);
+    /// Switches between liquid and gas without restarting. The parameters for
+    /// the new type are applied and the ghost layer is added or removed, while
+    /// the fluid particles stay where they are.
+    pub fn set_fluid_type(&mut self, fluid_type: FluidType) -> Result<(), ConfigError> {
+        let mut config = self.config.clone();
+        config.fluid_type = fluid_type;
+        config.validate()?;
+        config.adapt_to_fluid_type();
+
+        self.config = config;
+        self.fluid.apply_fluid_type(&self.config, &self.boundary);
+        Ok(())
+    }
+
+    /// Simulated time in seconds since the start of the run.
+    pub fn elapsed_time(&self) -> f32 {
+        self.elapsed_time
+    }
+
+    /// Number of fixed steps taken since the start of the run.
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
 
-        // Handle interaction before collecting particles
-        if let Some(click_point) = self.click_point {
-            if let Some(interaction_type) = &self.interaction_type {
-                self.fluid
-                    .handle_interaction(click_point, *interaction_type, &self.config);
+    /// Time spent in each phase of the steps taken since the timings were
+    /// last taken.
+    pub fn timings(&self) -> &PhaseTimings {
+        &self.timings
+    }
+
+    /// Returns the accumulated phase timings and starts accumulating anew.
+    pub fn take_timings(&mut self) -> PhaseTimings {
+        mem::take(&mut self.timings)
+    }
+
+    pub fn fluid(&self) -> &Fluid {
+        &self.fluid
+    }
+
+    pub fn boundary(&self) -> &Boundary {
+        &self.boundary
+    }
+
+    pub fn click_point(&self) -> Option<Vec2> {
+        self.click_point
+    }
+
+    pub fn set_interaction(&mut self, click_point: Vec2, interaction_type: InteractionType) {
+        self.click_point = Some(click_point);
+        self.interaction_type = Some(interaction_type);
+    }
+
+    pub fn clear_interaction(&mut self) {
+        self.click_point = None;
+        self.interaction_type = None;
+    }
+
+    /// Advances the simulation by a frame's worth of real time.
+    ///
+    /// Frame time is scaled by `time_scale` and accumulated, then consumed in
+    /// fixed steps of `time_step`. At most `max_steps_per_frame` steps run per
+    /// call; any time left over beyond that is dropped so that a long frame
+    /// hitch slows the simulation down instead of exploding it.
+    pub fn update(&mut self, frame_time: f32) {
+        self.update_with(frame_time, |_| ControlFlow::Continue(()));
+    }
+
+    /// Like `update`, but calls `before_step` before every fixed step so that
+    /// the caller can change the simulation at exact step boundaries, e.g. to
+    /// replay recorded input. Returning `ControlFlow::Break` stops stepping
+    /// for this frame and keeps the remaining time in the accumulator.
+    pub fn update_with(
+        &mut self,
+        frame_time: f32,
+        mut before_step: impl FnMut(&mut Self) -> ControlFlow<()>,
+    ) {
+        self.accumulator += frame_time * self.config.time_scale;
+
+        let mut steps = 0;
+        while self.accumulator >= self.config.time_step {
+            if steps >= self.config.max_steps_per_frame {
+                self.accumulator = 0.0;
+                break;
+            }
+            if before_step(self).is_break() {
+                break;
             }
+            self.step();
+            self.accumulator -= self.config.time_step;
+            steps += 1;
         }
-
-        self.boundary.check_collision(&mut self.fluid.particles);
     }
 
-    pub fn render(&self) {
-        self.boundary.draw();
-        self.fluid.draw();
-        self.draw_interaction_radius();
+    /// Advances the simulation by exactly one fixed `time_step`, split into
+    /// `substeps` solver iterations.
+    pub fn step(&mut self) {
+        let substeps = self.config.substeps.max(1);
+        let delta_time = self.config.time_step / substeps as f32;
+        for _ in 0..substeps {
+            self.substep(delta_time);
+        }
+        self.step_count += 1;
     }
 
-    pub fn draw_interaction_radius(&self) {
-        if let Some(point) = self.click_point {
-            let radius = self.config.interaction_radius;
-            draw_circle_lines(point.x, point.y, radius, 1., GREEN);
+    fn substep(&mut self, delta_time: f32) {
+        let mut remaining_time = delta_time;
+        while remaining_time > 0.0 {
+            let step_time = self.fluid.update(
+                remaining_time,
+                self.config.gravity,
+                &self.config,
+                &mut self.timings,
+            );
+            // A zero step would never use up the remaining time
+            if step_time <= 0.0 {
+                break;
+            }
+            remaining_time -= step_time;
+            self.elapsed_time += step_time;
+
+            // Handle interaction before collecting particles
+            if let Some(click_point) = self.click_point
+                && let Some(interaction_type) = self.interaction_type
+            {
+                self.timings.time(Phase::Interaction, || {
+                    self.fluid
+                        .handle_interaction(click_point, interaction_type, &self.config)
+                });
+            }
+
+            self.timings.time(Phase::Collision, || {
+                self.boundary.check_collision(&mut self.fluid.particles)
+            });
         }
     }
 }
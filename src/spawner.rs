@@ -1,12 +1,14 @@
-use macroquad::prelude::*;
-use macroquad::rand as macroquad_rand;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     boundary::Boundary,
     config::{Config, FluidSpawnMode},
     particle::Particle,
+    rng::Rng,
 };
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ParticleSpawner {
     time_to_next_spawn: f32,
     spawn_x_min: f32,
@@ -46,11 +48,18 @@ impl ParticleSpawner {
         }
     }
 
+    /// Gives back time that the last `update_flow_spawn` advanced by but the
+    /// fluid did not simulate, e.g. when the adaptive time step was shorter.
+    pub fn return_unused_time(&mut self, unused_time: f32) {
+        self.time_to_next_spawn += unused_time;
+    }
+
     pub fn update_flow_spawn(
         &mut self,
         particles: &mut Vec<Particle>,
         config: &Config,
         delta_time: f32,
+        rng: &mut Rng,
     ) {
         if config.fluid_spawn_mode != FluidSpawnMode::Flow
             || self.flow_rate_interval == f32::MAX
@@ -66,7 +75,7 @@ impl ParticleSpawner {
             if particles.len() >= config.particle_count as usize {
                 break;
             }
-            let spawn_x = macroquad_rand::gen_range(self.spawn_x_min, self.spawn_x_max);
+            let spawn_x = rng.gen_range(self.spawn_x_min, self.spawn_x_max);
             let spawn_position = Vec2::new(spawn_x, self.spawn_y);
 
             let new_particle = Particle::new(spawn_position, config.particle_radius, false);
@@ -0,0 +1,105 @@
+use glam::Vec2;
+use rayon::prelude::*;
+
+use crate::{boundary::Boundary, config::Config, fluid::Fluid, physics::Physics};
+
+/// A continuous fluid surface reconstructed from the particles by marching
+/// squares over a density lattice, as filled triangles and outline segments
+/// in world space.
+pub struct Surface {
+    pub triangles: Vec<[Vec2; 3]>,
+    pub outline: Vec<[Vec2; 2]>,
+}
+
+impl Surface {
+    /// Samples the density of the fluid particles (ghosts excluded) on a
+    /// lattice covering the boundary with `surface.resolution` spacing, and
+    /// traces the contour where it crosses `surface.threshold *
+    /// target_density`.
+    pub fn extract(fluid: &Fluid, boundary: &Boundary, config: &Config) -> Self {
+        let spacing = config.surface.resolution;
+        let cols = (boundary.width / spacing).ceil() as usize + 1;
+        let rows = (boundary.height / spacing).ceil() as usize + 1;
+        let point_at = |col: usize, row: usize| {
+            let offset = Vec2::new(col as f32 * spacing, row as f32 * spacing);
+            (boundary.pos + offset).min(boundary.pos + Vec2::new(boundary.width, boundary.height))
+        };
+
+        let values: Vec<f32> = (0..rows * cols)
+            .into_par_iter()
+            .map(|index| density_at(fluid, config, point_at(index % cols, index / cols)))
+            .collect();
+
+        let iso = config.surface.threshold * config.target_density;
+        let mut surface = Self {
+            triangles: Vec::new(),
+            outline: Vec::new(),
+        };
+        for row in 0..rows.saturating_sub(1) {
+            for col in 0..cols.saturating_sub(1) {
+                // Corners clockwise from the top left
+                let corners = [
+                    (col, row),
+                    (col + 1, row),
+                    (col + 1, row + 1),
+                    (col, row + 1),
+                ]
+                .map(|(x, y)| (point_at(x, y), values[y * cols + x]));
+                surface.add_cell(corners, iso);
+            }
+        }
+        surface
+    }
+
+    /// Adds the part of a lattice cell where the density is at least `iso`.
+    ///
+    /// Walking around the cell and keeping the inside corners plus the
+    /// crossing points on each edge gives a convex polygon (saddle cells are
+    /// joined through the middle), so it can be filled as a triangle fan. Two
+    /// crossing points next to each other on the polygon are a piece of the
+    /// contour.
+    fn add_cell(&mut self, corners: [(Vec2, f32); 4], iso: f32) {
+        let mut polygon: Vec<(Vec2, bool)> = Vec::with_capacity(6);
+        for i in 0..4 {
+            let (start, start_value) = corners[i];
+            let (end, end_value) = corners[(i + 1) % 4];
+            let start_inside = start_value >= iso;
+            if start_inside {
+                polygon.push((start, false));
+            }
+            if start_inside != (end_value >= iso) {
+                let t = (iso - start_value) / (end_value - start_value);
+                polygon.push((start.lerp(end, t), true));
+            }
+        }
+        if polygon.len() < 3 {
+            return;
+        }
+
+        for i in 1..polygon.len() - 1 {
+            self.triangles
+                .push([polygon[0].0, polygon[i].0, polygon[i + 1].0]);
+        }
+        for i in 0..polygon.len() {
+            let (a, a_is_crossing) = polygon[i];
+            let (b, b_is_crossing) = polygon[(i + 1) % polygon.len()];
+            if a_is_crossing && b_is_crossing {
+                self.outline.push([a, b]);
+            }
+        }
+    }
+}
+
+/// Density of the fluid particles around `point`, from their drawn positions.
+fn density_at(fluid: &Fluid, config: &Config, point: Vec2) -> f32 {
+    let (grid_x, grid_y) = fluid.get_grid_coords(point);
+    fluid
+        .get_neighbor_particle_indices(grid_x, grid_y)
+        .into_iter()
+        .map(|index| &fluid.particles[index])
+        .filter(|particle| !particle.is_ghost)
+        .fold(0.0, |density, particle| {
+            let distance = point.distance(particle.position);
+            density + config.mass * Physics::density_kernel(config.smoothing_radius, distance)
+        })
+}
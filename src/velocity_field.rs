@@ -0,0 +1,199 @@
+use clap::ValueEnum;
+use glam::Vec2;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{boundary::Boundary, config::Config, fluid::Fluid};
+
+pub const ARROW_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+pub const STREAMLINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.6];
+/// Arrow lattice spacing and streamline seed spacing, in smoothing radii.
+const ARROW_SPACING: f32 = 1.0;
+const STREAMLINE_SPACING: f32 = 2.0;
+/// Streamline integration step, in smoothing radii.
+const STREAMLINE_STEP: f32 = 0.25;
+const MAX_STREAMLINE_STEPS: usize = 80;
+
+/// How the velocity field overlay is drawn.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum VelocityFieldMode {
+    Off,
+    Arrows,
+    Streamlines,
+}
+
+impl VelocityFieldMode {
+    pub fn next(self) -> Self {
+        match self {
+            VelocityFieldMode::Off => VelocityFieldMode::Arrows,
+            VelocityFieldMode::Arrows => VelocityFieldMode::Streamlines,
+            VelocityFieldMode::Streamlines => VelocityFieldMode::Off,
+        }
+    }
+
+    pub fn color(self) -> [f32; 4] {
+        match self {
+            VelocityFieldMode::Off | VelocityFieldMode::Arrows => ARROW_COLOR,
+            VelocityFieldMode::Streamlines => STREAMLINE_COLOR,
+        }
+    }
+}
+
+/// A line from `start` to `end` with a filled triangular head at `end`.
+pub struct Arrow {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub head: [Vec2; 3],
+}
+
+impl Arrow {
+    /// An arrow along `vector`, or `None` if it is too short to draw.
+    pub fn new(start: Vec2, vector: Vec2) -> Option<Self> {
+        let length = vector.length();
+        if length < 1.0 {
+            return None;
+        }
+        let end = start + vector;
+        let direction = vector / length;
+        let head_size = (length * 0.3).min(6.0);
+        let left = end - direction * head_size + direction.perp() * head_size * 0.5;
+        let right = end - direction * head_size - direction.perp() * head_size * 0.5;
+        Some(Self {
+            start,
+            end,
+            head: [end, left, right],
+        })
+    }
+}
+
+/// The interpolated velocity field inside the boundary as shapes in world
+/// space, shared by the interactive view and the headless images.
+pub struct VelocityField {
+    pub arrows: Vec<Arrow>,
+    /// Streamlines as polylines, each marked with an arrow at its downstream
+    /// end in `arrows`.
+    pub streamlines: Vec<Vec<Vec2>>,
+}
+
+impl VelocityField {
+    pub fn compute(
+        mode: VelocityFieldMode,
+        fluid: &Fluid,
+        boundary: &Boundary,
+        config: &Config,
+    ) -> Self {
+        match mode {
+            VelocityFieldMode::Off => Self {
+                arrows: Vec::new(),
+                streamlines: Vec::new(),
+            },
+            VelocityFieldMode::Arrows => Self {
+                arrows: arrows(fluid, boundary, config),
+                streamlines: Vec::new(),
+            },
+            VelocityFieldMode::Streamlines => {
+                let streamlines = streamlines(fluid, boundary, config);
+                // Mark the downstream end so the direction of flow is visible
+                let arrows = streamlines
+                    .iter()
+                    .filter_map(|streamline| match streamline[..] {
+                        [.., before_last, last] => Arrow::new(before_last, last - before_last),
+                        _ => None,
+                    })
+                    .collect();
+                Self {
+                    arrows,
+                    streamlines,
+                }
+            }
+        }
+    }
+}
+
+/// Points of a regular lattice with the given spacing, centred in the boundary.
+fn lattice(boundary: &Boundary, spacing: f32) -> Vec<Vec2> {
+    let cols = (boundary.width / spacing).floor() as usize;
+    let rows = (boundary.height / spacing).floor() as usize;
+    let margin = Vec2::new(
+        boundary.width - (cols as f32 - 1.0) * spacing,
+        boundary.height - (rows as f32 - 1.0) * spacing,
+    ) / 2.0;
+    let origin = boundary.pos + margin;
+
+    (0..rows)
+        .flat_map(|row| {
+            (0..cols).map(move |col| origin + Vec2::new(col as f32, row as f32) * spacing)
+        })
+        .collect()
+}
+
+fn arrows(fluid: &Fluid, boundary: &Boundary, config: &Config) -> Vec<Arrow> {
+    let spacing = config.smoothing_radius * ARROW_SPACING;
+    let samples: Vec<(Vec2, Vec2)> = lattice(boundary, spacing)
+        .into_par_iter()
+        .filter_map(|point| Some((point, fluid.velocity_at(point, config)?)))
+        .collect();
+
+    // Arrows are scaled so that the fastest sample spans most of a lattice cell
+    let max_speed = samples
+        .iter()
+        .map(|(_, velocity)| velocity.length())
+        .fold(0.0, f32::max);
+    if max_speed <= 0.0 {
+        return Vec::new();
+    }
+
+    let scale = spacing * 0.9 / max_speed;
+    samples
+        .into_iter()
+        .filter_map(|(point, velocity)| Arrow::new(point, velocity * scale))
+        .collect()
+}
+
+fn streamlines(fluid: &Fluid, boundary: &Boundary, config: &Config) -> Vec<Vec<Vec2>> {
+    let spacing = config.smoothing_radius * STREAMLINE_SPACING;
+    let step = config.smoothing_radius * STREAMLINE_STEP;
+    lattice(boundary, spacing)
+        .into_par_iter()
+        .map(|seed| trace_streamline(fluid, boundary, config, seed, step))
+        .collect()
+}
+
+/// Follows the flow direction from `seed` with midpoint (RK2) steps of fixed
+/// length, stopping outside the fluid or the boundary.
+fn trace_streamline(
+    fluid: &Fluid,
+    boundary: &Boundary,
+    config: &Config,
+    seed: Vec2,
+    step: f32,
+) -> Vec<Vec2> {
+    let direction_at = |point: Vec2| {
+        fluid
+            .velocity_at(point, config)
+            .and_then(|velocity| velocity.try_normalize())
+    };
+    let is_inside = |point: Vec2| {
+        point.x >= boundary.pos.x
+            && point.y >= boundary.pos.y
+            && point.x <= boundary.pos.x + boundary.width
+            && point.y <= boundary.pos.y + boundary.height
+    };
+
+    let mut points = vec![seed];
+    let mut point = seed;
+    for _ in 0..MAX_STREAMLINE_STEPS {
+        let Some(direction) = direction_at(point) else {
+            break;
+        };
+        let Some(midpoint_direction) = direction_at(point + direction * step * 0.5) else {
+            break;
+        };
+        point += midpoint_direction * step;
+        if !is_inside(point) {
+            break;
+        }
+        points.push(point);
+    }
+    points
+}
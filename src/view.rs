@@ -0,0 +1,69 @@
+use macroquad::prelude::*;
+
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 50.0;
+
+/// Pan and zoom on top of a camera that fits the whole domain into the window.
+pub struct View {
+    zoom: f32,
+    /// Offset of the view centre from the domain centre, in world units.
+    pan: Vec2,
+    /// Screen position of the mouse on the previous frame of a middle-drag.
+    drag_position: Option<Vec2>,
+}
+
+impl View {
+    pub fn new() -> Self {
+        Self {
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+            drag_position: None,
+        }
+    }
+
+    /// Shows the whole domain again.
+    pub fn reset(&mut self) {
+        self.zoom = 1.0;
+        self.pan = Vec2::ZERO;
+    }
+
+    /// The camera for the domain `(0, 0)..domain_size`. At a zoom of 1 the
+    /// domain is scaled uniformly to fit the window and centred in it.
+    pub fn camera(&self, domain_size: Vec2) -> Camera2D {
+        let screen_size = Vec2::new(screen_width(), screen_height());
+        let scale = (screen_size / domain_size).min_element() * self.zoom;
+        Camera2D {
+            target: domain_size / 2.0 + self.pan,
+            zoom: 2.0 * scale / screen_size,
+            ..Default::default()
+        }
+    }
+
+    /// Zooms with the mouse wheel, keeping the point under the cursor in
+    /// place, and pans while the middle mouse button is held.
+    pub fn handle_input(&mut self, domain_size: Vec2, accepts_mouse: bool) {
+        let mouse_pos = Vec2::from(mouse_position());
+
+        let wheel = mouse_wheel().1;
+        if accepts_mouse && wheel != 0.0 {
+            let anchor = self.camera(domain_size).screen_to_world(mouse_pos);
+            self.zoom = (self.zoom * ZOOM_STEP.powf(wheel.signum())).clamp(MIN_ZOOM, MAX_ZOOM);
+            let moved_anchor = self.camera(domain_size).screen_to_world(mouse_pos);
+            self.pan += anchor - moved_anchor;
+        }
+
+        if is_mouse_button_down(MouseButton::Middle) {
+            if let Some(drag_position) = self.drag_position {
+                let camera = self.camera(domain_size);
+                self.pan +=
+                    camera.screen_to_world(drag_position) - camera.screen_to_world(mouse_pos);
+                self.drag_position = Some(mouse_pos);
+            } else if accepts_mouse {
+                self.drag_position = Some(mouse_pos);
+            }
+        } else {
+            self.drag_position = None;
+        }
+    }
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{small_config, state_bits};
+use fluid_simulation::{
+    checkpoint::Checkpoint,
+    config::{Config, FluidSpawnMode, FluidType},
+    simulation::Simulation,
+};
+use std::fs;
+
+const STEPS_BEFORE: u32 = 30;
+const STEPS_AFTER: u32 = 30;
+
+/// Runs `STEPS_BEFORE` steps, saves a checkpoint to `file_name` and loads it
+/// into a new simulation that runs `STEPS_AFTER` more, and checks that it ends
+/// in the same state as a run that was never interrupted.
+fn assert_resumes_bit_identically(config: Config, prepare: fn(&mut Simulation), file_name: &str) {
+    let mut uninterrupted = Simulation::new(config.clone());
+    prepare(&mut uninterrupted);
+    for _ in 0..STEPS_BEFORE + STEPS_AFTER {
+        uninterrupted.step();
+    }
+
+    let mut first_half = Simulation::new(config);
+    prepare(&mut first_half);
+    for _ in 0..STEPS_BEFORE {
+        first_half.step();
+    }
+    let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), file_name));
+    first_half
+        .to_checkpoint()
+        .save(&path)
+        .expect("checkpoint saves");
+    let checkpoint = Checkpoint::load(&path).expect("checkpoint loads");
+    fs::remove_file(&path).expect("checkpoint file is removed");
+
+    let mut resumed = Simulation::from_checkpoint(checkpoint);
+    for _ in 0..STEPS_AFTER {
+        resumed.step();
+    }
+
+    assert_eq!(resumed.step_count(), uninterrupted.step_count());
+    assert_eq!(
+        resumed.elapsed_time().to_bits(),
+        uninterrupted.elapsed_time().to_bits()
+    );
+    assert_eq!(
+        state_bits(&resumed.fluid().particles),
+        state_bits(&uninterrupted.fluid().particles)
+    );
+}
+
+#[test]
+fn resumed_liquid_flow_matches_uninterrupted_run() {
+    let config = Config {
+        fluid_spawn_mode: FluidSpawnMode::Flow,
+        ..small_config(3)
+    };
+    assert_resumes_bit_identically(config, |_| {}, "liquid-flow.json");
+}
+
+#[test]
+fn resumed_gas_after_switch_matches_uninterrupted_run() {
+    assert_resumes_bit_identically(
+        small_config(3),
+        |simulation| {
+            simulation
+                .set_fluid_type(FluidType::Gas)
+                .expect("gas is valid for grid spawning")
+        },
+        "gas.json",
+    );
+}
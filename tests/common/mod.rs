@@ -0,0 +1,32 @@
+use fluid_simulation::{config::Config, particle::Particle};
+
+/// The default config with fewer particles in a smaller domain, so that
+/// debug builds get through a few dozen steps quickly.
+pub fn small_config(seed: u64) -> Config {
+    let mut config = Config {
+        particle_count: 400,
+        domain_width: 640.0,
+        domain_height: 360.0,
+        seed,
+        ..Config::default()
+    };
+    config.validate().expect("test config is valid");
+    config.adapt_to_fluid_type();
+    config
+}
+
+/// Positions and velocities as raw bits, so that the comparison is exact and
+/// NaNs compare equal to themselves.
+pub fn state_bits(particles: &[Particle]) -> Vec<[u32; 4]> {
+    particles
+        .iter()
+        .map(|p| {
+            [
+                p.position.x.to_bits(),
+                p.position.y.to_bits(),
+                p.velocity.x.to_bits(),
+                p.velocity.y.to_bits(),
+            ]
+        })
+        .collect()
+}
@@ -0,0 +1,91 @@
+use fluid_simulation::{
+    config::Config,
+    export::{self, ExportFormat},
+    particle::Particle,
+};
+use glam::Vec2;
+use std::fs;
+
+/// Two fluid particles with distinct values and a ghost between them, which
+/// every format leaves out.
+fn particles() -> Vec<Particle> {
+    let mut first = Particle::new(Vec2::new(1.5, 2.0), 3.0, false);
+    first.velocity = Vec2::new(0.25, -1.0);
+    first.density = 10.0;
+    let ghost = Particle::new(Vec2::new(99.0, 99.0), 3.0, true);
+    let mut second = Particle::new(Vec2::new(4.0, 8.5), 3.0, false);
+    second.velocity = Vec2::new(-2.0, 0.5);
+    second.density = 20.0;
+    vec![first, ghost, second]
+}
+
+/// Writes the test particles in `format` and returns the file's lines.
+fn write(format: ExportFormat) -> Vec<String> {
+    let mut config = Config::default();
+    config.adapt_to_fluid_type();
+    let path = std::env::temp_dir().join(format!(
+        "{}-export.{}",
+        std::process::id(),
+        format.extension()
+    ));
+    export::write_frame(&path, format, &particles(), &config).expect("frame is written");
+    let contents = fs::read_to_string(&path).expect("frame is readable");
+    fs::remove_file(&path).expect("frame file is removed");
+    contents.lines().map(str::to_owned).collect()
+}
+
+/// The lines following `header`, up to the next keyword line.
+fn section<'a>(lines: &'a [String], header: &str) -> Vec<&'a str> {
+    let start = lines
+        .iter()
+        .position(|line| line == header)
+        .unwrap_or_else(|| panic!("missing '{}' in {:?}", header, lines));
+    lines[start + 1..]
+        .iter()
+        .take_while(|line| !line.starts_with(|c: char| c.is_ascii_alphabetic()))
+        .map(String::as_str)
+        .collect()
+}
+
+#[test]
+fn csv_has_a_header_and_a_row_per_fluid_particle() {
+    let lines = write(ExportFormat::Csv);
+    assert_eq!(lines[0], "x,y,vx,vy,density,near_density,pressure");
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with("1.5,2,0.25,-1,10,0,"));
+    assert!(lines[2].starts_with("4,8.5,-2,0.5,20,0,"));
+}
+
+#[test]
+fn vtk_has_a_point_and_vertex_per_fluid_particle() {
+    let lines = write(ExportFormat::Vtk);
+    assert_eq!(lines[0], "# vtk DataFile Version 3.0");
+    assert_eq!(section(&lines, "POINTS 2 float"), ["1.5 2 0", "4 8.5 0"]);
+    assert_eq!(section(&lines, "VERTICES 2 4"), ["1 0", "1 1"]);
+    assert!(lines.contains(&"POINT_DATA 2".to_owned()));
+    assert_eq!(
+        section(&lines, "VECTORS velocity float"),
+        ["0.25 -1 0", "-2 0.5 0"]
+    );
+    // Density is the first scalar field
+    let density = lines
+        .iter()
+        .position(|line| line == "SCALARS density float 1")
+        .expect("density is written");
+    assert_eq!(lines[density + 1], "LOOKUP_TABLE default");
+    assert_eq!(lines[density + 2..density + 4], ["10", "20"]);
+}
+
+#[test]
+fn ply_has_a_vertex_per_fluid_particle() {
+    let lines = write(ExportFormat::Ply);
+    assert_eq!(lines[..3], ["ply", "format ascii 1.0", "element vertex 2"]);
+    let end_header = lines
+        .iter()
+        .position(|line| line == "end_header")
+        .expect("header ends");
+    let vertices = &lines[end_header + 1..];
+    assert_eq!(vertices.len(), 2);
+    assert!(vertices[0].starts_with("1.5 2 0 0.25 -1 10 0 "));
+    assert!(vertices[1].starts_with("4 8.5 0 -2 0.5 20 0 "));
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "window")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A fresh, empty output directory for one test.
+fn output_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn zero_snapshot_interval_writes_no_snapshots() {
+    let dir = output_dir("no-snapshots");
+    let status = Command::new(env!("CARGO_BIN_EXE_fluid-simulation"))
+        .args(["--headless", "--steps", "3", "--snapshot-interval", "0"])
+        .args(["--set", "particle_count=100"])
+        .arg("--output-dir")
+        .arg(&dir)
+        .status()
+        .expect("runner starts");
+    assert!(status.success());
+
+    let files: Vec<_> = fs::read_dir(&dir)
+        .expect("output directory is created")
+        .map(|entry| entry.expect("entry is readable").file_name())
+        .collect();
+    fs::remove_dir_all(&dir).expect("output directory is removed");
+    assert!(files.is_empty(), "unexpected output: {:?}", files);
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{small_config, state_bits};
+use fluid_simulation::{
+    config::InteractionType,
+    replay::{InputEvent, Player, Recorder, Replay},
+    simulation::Simulation,
+};
+use glam::Vec2;
+use std::fs;
+
+/// Frames after which a playback that has not caught up counts as stalled.
+const MAX_FRAMES: u32 = 1000;
+
+/// Applies `event` the way the window does and records it with the step
+/// count it was applied at.
+fn apply(simulation: &mut Simulation, recorder: &mut Recorder, event: InputEvent) {
+    let step = simulation.step_count();
+    match &event {
+        InputEvent::Interact {
+            point,
+            interaction_type,
+        } => simulation.set_interaction(*point, *interaction_type),
+        InputEvent::ClearInteraction => simulation.clear_interaction(),
+        InputEvent::SetFluidType(fluid_type) => simulation
+            .set_fluid_type(*fluid_type)
+            .expect("fluid type switches"),
+        InputEvent::TogglePause => {}
+        InputEvent::Step(steps) => {
+            for _ in 0..*steps {
+                simulation.step();
+            }
+        }
+        InputEvent::ConfigChanged(config) => simulation.apply_tunable_config(config),
+    }
+    recorder.record(step, event);
+}
+
+fn run(simulation: &mut Simulation, steps: u32) {
+    for _ in 0..steps {
+        simulation.step();
+    }
+}
+
+#[test]
+fn replay_reproduces_recorded_session() {
+    let mut recorded = Simulation::new(small_config(3));
+    run(&mut recorded, 5);
+    let mut recorder = Recorder::start(&recorded, false);
+    let center = recorded.boundary().pos
+        + Vec2::new(recorded.boundary().width, recorded.boundary().height) / 2.0;
+
+    let pull = |point| InputEvent::Interact {
+        point,
+        interaction_type: InteractionType::Pull,
+    };
+    apply(&mut recorded, &mut recorder, pull(center));
+    run(&mut recorded, 10);
+    apply(
+        &mut recorded,
+        &mut recorder,
+        pull(center + Vec2::new(40.0, 0.0)),
+    );
+    run(&mut recorded, 5);
+    apply(&mut recorded, &mut recorder, InputEvent::ClearInteraction);
+    run(&mut recorded, 5);
+
+    // Paused: single steps and a burst, with input and a parameter change
+    // in between
+    apply(&mut recorded, &mut recorder, InputEvent::TogglePause);
+    apply(&mut recorded, &mut recorder, InputEvent::Step(1));
+    apply(
+        &mut recorded,
+        &mut recorder,
+        InputEvent::Interact {
+            point: center,
+            interaction_type: InteractionType::Push,
+        },
+    );
+    apply(&mut recorded, &mut recorder, InputEvent::Step(10));
+    let mut config = recorded.config().clone();
+    config.liquid.viscosity_strength += 0.5;
+    config.boundary_damping *= 0.5;
+    apply(
+        &mut recorded,
+        &mut recorder,
+        InputEvent::ConfigChanged(Box::new(config)),
+    );
+    apply(&mut recorded, &mut recorder, InputEvent::Step(1));
+    apply(&mut recorded, &mut recorder, InputEvent::ClearInteraction);
+    apply(&mut recorded, &mut recorder, InputEvent::TogglePause);
+    run(&mut recorded, 10);
+
+    let path = std::env::temp_dir().join(format!("{}-replay.json", std::process::id()));
+    recorder.finish().save(&path).expect("replay saves");
+    let replay = Replay::load(&path).expect("replay loads");
+    fs::remove_file(&path).expect("replay file is removed");
+
+    // Played back the way the window does, where only the recorded steps
+    // move the simulation on while it is paused
+    let (mut player, initial_state) = Player::new(replay);
+    let mut replayed = Simulation::from_checkpoint(initial_state);
+    let mut is_paused = player.starts_paused();
+    let mut frames = 0;
+    while replayed.step_count() < recorded.step_count() {
+        frames += 1;
+        assert!(
+            frames <= MAX_FRAMES,
+            "playback stalled at step {}",
+            replayed.step_count()
+        );
+        if player.apply_due(&mut replayed) % 2 == 1 {
+            is_paused = !is_paused;
+        }
+        if !is_paused {
+            replayed.step();
+        }
+    }
+
+    assert!(player.is_finished());
+    assert_eq!(replayed.step_count(), recorded.step_count());
+    assert_eq!(replayed.config(), recorded.config());
+    assert_eq!(
+        state_bits(&replayed.fluid().particles),
+        state_bits(&recorded.fluid().particles)
+    );
+}